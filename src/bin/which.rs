@@ -0,0 +1,329 @@
+//! A GNU `which` compatible command line interface built on top of [`which::WhichConfig`].
+//!
+//! Only available when feature `cli` is enabled.
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+
+use which::sys::{RealSys, Sys};
+use which::{Query, QueryScope, WhichConfig};
+
+const USAGE: &str = "\
+Usage: which [options] [--] COMMAND [...]
+Write the full path of COMMAND(s) to standard output.
+
+  --version, -[vV] Print version and exit successfully.
+  --help,          Print this help and exit successfully.
+  --skip-dot       Skip directories in PATH that start with a dot.
+  --skip-tilde     Skip directories in PATH that start with a tilde.
+  --show-dot       Don't expand a dot to current directory in output.
+  --show-tilde     Output a tilde for HOME directory for non-root.
+  --tty-only       Stop processing options on the right if not on tty.
+  --all, -a        Print all matches in PATH, not just the first.
+  --json           Print the results as a JSON array.
+  --scoped         Accept scoped COMMANDs, always on for Windows.
+
+A scoped COMMAND works like `where` on Windows: DIR:PATTERN searches only
+DIR, and $VAR:PATTERN the path list in environment variable VAR. PATTERN
+may contain the wildcards *, ? and [...].
+
+Exit status is the number of COMMANDs that could not be found,
+or 255 if no COMMAND was given.";
+
+/// Exit code used for usage errors, mirroring GNU `which` returning -1.
+const USAGE_ERROR: u8 = 255;
+
+#[derive(Default)]
+struct Options {
+    all: bool,
+    skip_dot: bool,
+    skip_tilde: bool,
+    show_dot: bool,
+    show_tilde: bool,
+    json: bool,
+    /// Interpret `DIR:PATTERN` and `$VAR:PATTERN`. Off by default outside Windows, where `:` can
+    /// be part of a command name.
+    scoped: bool,
+}
+
+enum Command {
+    Lookup(Options, Vec<OsString>),
+    Help,
+    Version,
+}
+
+fn parse_args(args: impl IntoIterator<Item = OsString>, is_tty: bool) -> Result<Command, String> {
+    let mut options = Options {
+        scoped: cfg!(windows),
+        ..Options::default()
+    };
+    let mut names = Vec::new();
+    let mut ignore_options = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.to_str().filter(|a| a.starts_with('-') && a.len() > 1) else {
+            names.push(arg);
+            continue;
+        };
+        if flag == "--" {
+            names.extend(args);
+            break;
+        }
+        let mut apply = |name: &str| -> Result<Option<Command>, String> {
+            if name == "tty-only" {
+                ignore_options |= !is_tty;
+                return Ok(None);
+            }
+            match name {
+                "help" => return Ok(Some(Command::Help)),
+                "version" | "v" | "V" => return Ok(Some(Command::Version)),
+                "all" | "a" | "skip-dot" | "skip-tilde" | "show-dot" | "show-tilde" | "json"
+                | "scoped" => {}
+                _ => return Err(format!("unrecognized option '{flag}'")),
+            }
+            if ignore_options {
+                return Ok(None);
+            }
+            match name {
+                "all" | "a" => options.all = true,
+                "skip-dot" => options.skip_dot = true,
+                "skip-tilde" => options.skip_tilde = true,
+                "show-dot" => options.show_dot = true,
+                "show-tilde" => options.show_tilde = true,
+                "scoped" => options.scoped = true,
+                _ => options.json = true,
+            }
+            Ok(None)
+        };
+        if let Some(long) = flag.strip_prefix("--") {
+            if let Some(command) = apply(long)? {
+                return Ok(command);
+            }
+        } else {
+            // Short options may be bundled, e.g. `-av`.
+            for short in flag[1..].chars() {
+                if let Some(command) = apply(short.encode_utf8(&mut [0; 4]))? {
+                    return Ok(command);
+                }
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return Err("no command given".to_owned());
+    }
+    Ok(Command::Lookup(options, names))
+}
+
+/// Environment shared between every looked up name.
+struct Context {
+    entries: Vec<PathBuf>,
+    home: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+    show_tilde: bool,
+}
+
+impl Context {
+    fn new(options: &Options) -> Self {
+        let home = RealSys.home_dir();
+        let entries = RealSys
+            .env_path()
+            .map(|paths| RealSys.env_split_paths(&paths))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| !(options.skip_dot && is_dot_entry(entry)))
+            .filter(|entry| {
+                !(options.skip_tilde
+                    && (entry.starts_with("~")
+                        || home.as_ref().is_some_and(|home| entry.starts_with(home))))
+            })
+            .collect();
+        Self {
+            entries,
+            home,
+            cwd: RealSys.current_dir().ok(),
+            show_tilde: options.show_tilde && !is_root(),
+        }
+    }
+
+    fn lookup(&self, name: &OsStr, options: &Options) -> Vec<PathBuf> {
+        let scoped = name
+            .to_str()
+            .filter(|_| options.scoped)
+            .and_then(|name| Query::parse(name).ok())
+            .filter(|query| query.scope != QueryScope::PathList);
+        if let Some(query) = scoped {
+            let results = WhichConfig::new().query(query).all_results();
+            return results
+                .into_iter()
+                .flatten()
+                .take(if options.all { usize::MAX } else { 1 })
+                .map(|path| self.display_path(path))
+                .collect();
+        }
+        if Path::new(name).components().count() > 1 {
+            // Names with a path separator are resolved against the cwd, not PATH.
+            let results = WhichConfig::new()
+                .binary_name(name.to_os_string())
+                .all_results();
+            return results
+                .into_iter()
+                .flatten()
+                .take(if options.all { usize::MAX } else { 1 })
+                .map(|path| self.display_path(path))
+                .collect();
+        }
+
+        let mut found = Vec::new();
+        for entry in &self.entries {
+            // Query one entry at a time so we know which entry each match came from.
+            let results = WhichConfig::new()
+                .binary_name(name.to_os_string())
+                .custom_path_list(entry.clone().into_os_string())
+                .system_cwd(false)
+                .all_results();
+            for path in results.into_iter().flatten() {
+                let path = if path.is_relative() && !(options.show_dot && is_dot_entry(entry)) {
+                    match &self.cwd {
+                        Some(cwd) => cwd.join(strip_cur_dir(&path)),
+                        None => path,
+                    }
+                } else if path.is_relative() {
+                    Path::new(".").join(strip_cur_dir(&path))
+                } else {
+                    path
+                };
+                found.push(self.display_path(path));
+                if !options.all {
+                    return found;
+                }
+            }
+        }
+        found
+    }
+
+    fn display_path(&self, path: PathBuf) -> PathBuf {
+        match &self.home {
+            Some(home) if self.show_tilde => match path.strip_prefix(home) {
+                Ok(rest) => Path::new("~").join(rest),
+                Err(_) => path,
+            },
+            _ => path,
+        }
+    }
+}
+
+fn is_dot_entry(entry: &Path) -> bool {
+    // An empty entry means the current directory, just like `.`.
+    entry.as_os_str().is_empty() || entry.to_string_lossy().starts_with('.')
+}
+
+fn strip_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+fn write_json_string(out: &mut impl Write, s: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+    write!(out, "\"")
+}
+
+fn write_json(out: &mut impl Write, results: &[(OsString, Vec<PathBuf>)]) -> io::Result<()> {
+    write!(out, "[")?;
+    for (i, (name, paths)) in results.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "{{\"name\":")?;
+        write_json_string(out, &name.to_string_lossy())?;
+        write!(out, ",\"found\":{},\"paths\":[", !paths.is_empty())?;
+        for (j, path) in paths.iter().enumerate() {
+            if j > 0 {
+                write!(out, ",")?;
+            }
+            write_json_string(out, &path.to_string_lossy())?;
+        }
+        write!(out, "]}}")?;
+    }
+    writeln!(out, "]")
+}
+
+fn main() -> ExitCode {
+    let (options, names) = match parse_args(std::env::args_os().skip(1), io::stdout().is_terminal())
+    {
+        Ok(Command::Lookup(options, names)) => (options, names),
+        Ok(Command::Help) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Version) => {
+            println!("which (which-rs) {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("which: {e}\n{USAGE}");
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+
+    let context = Context::new(&options);
+    let results = names
+        .into_iter()
+        .map(|name| {
+            let paths = context.lookup(&name, &options);
+            (name, paths)
+        })
+        .collect::<Vec<_>>();
+
+    let mut stdout = io::stdout().lock();
+    let written = if options.json {
+        write_json(&mut stdout, &results)
+    } else {
+        results
+            .iter()
+            .flat_map(|(_, paths)| paths)
+            .try_for_each(|path| writeln!(stdout, "{}", path.display()))
+    };
+    if written.is_err() {
+        return ExitCode::FAILURE;
+    }
+
+    let path_list = RealSys.env_path().unwrap_or_default();
+    let mut missing = 0usize;
+    for (name, _) in results.iter().filter(|(_, paths)| paths.is_empty()) {
+        missing += 1;
+        if !options.json {
+            eprintln!(
+                "which: no {} in ({})",
+                name.to_string_lossy(),
+                path_list.to_string_lossy()
+            );
+        }
+    }
+    ExitCode::from(missing.min(USAGE_ERROR as usize - 1) as u8)
+}
@@ -0,0 +1,309 @@
+use crate::finder::{
+    path_extensions, search_dirs, Finder, PathExt, PathListEdits, SearchDir, WhichFindIterator,
+};
+use crate::resolver::Locked;
+use crate::sys::{Sys, SysMetadata};
+use crate::{
+    CandidateFilter, Checker, CwdOption, Error, NonFatalErrorHandler, PathExtensions,
+    PathListOption, Result, SearchContext,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A [`WhichConfig`](crate::WhichConfig) that remembers the results of its queries, like a
+/// shell's command hash table.
+///
+/// Results are keyed on the name, the cwd and the path list and `PATHEXT` extensions in effect,
+/// which are read again for every query, so changing `PATH` or the cwd never returns a stale
+/// result. An entry is thrown away when a directory that could change it has a different
+/// modification time, as reported by [`SysMetadata::modified`], or when it's older than the
+/// [`ttl`](CachingResolver::ttl). For [`resolve`](CachingResolver::resolve) those are the
+/// directories up to the one the result was found in, and for
+/// [`resolve_all`](CachingResolver::resolve_all) every directory searched. Changes that don't
+/// touch a directory, like making a file executable, are only noticed once the entry expires.
+/// [`rehash`](CachingResolver::rehash) forgets everything, like `hash -r`.
+///
+/// When the modification time of one of those directories can't be read, e.g. because the
+/// [`Sys`] doesn't support it, changes to it can't be noticed, so the entry is only kept until
+/// the ttl passes, or not at all when there's no ttl.
+///
+/// Built with [`WhichConfig::caching_resolver`](crate::WhichConfig::caching_resolver). Queries
+/// take `&self`, and like a [`Resolver`](crate::Resolver), a caching resolver is `Send` and
+/// `Sync` as long as its [`Sys`], checker, filter and error handler are. The filter and the
+/// error handler are only called when a query isn't answered from the cache, and candidates are
+/// checked one after another, as [`WhichConfig::parallel`](crate::WhichConfig::parallel)
+/// doesn't apply to it.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "real-sys")]
+/// # {
+/// use std::time::Duration;
+/// use which::WhichConfig;
+///
+/// let resolver = WhichConfig::new()
+///     .caching_resolver()
+///     .ttl(Duration::from_secs(30));
+/// for _ in 0..1000 {
+///     // Only the first query searches the path list.
+///     let _ = resolver.resolve("cargo");
+/// }
+/// # }
+/// ```
+pub struct CachingResolver<TSys: Sys, F, C, K> {
+    sys: TSys,
+    path_list: PathListOption,
+    cwd: CwdOption,
+    path_list_edits: PathListEdits,
+    path_extensions: Option<PathExtensions>,
+    refuse_relative_path_entries: bool,
+    ttl: Option<Duration>,
+    cache: Mutex<HashMap<Key, Entry>>,
+    nonfatal_error_handler: Mutex<F>,
+    filter: Mutex<C>,
+    checker: K,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    binary_name: OsString,
+    all: bool,
+    cwd: Option<PathBuf>,
+    /// The fingerprint of the environment: the path list and `PATHEXT` extensions.
+    paths: Option<OsString>,
+    path_extensions: Vec<String>,
+}
+
+struct Entry {
+    outcome: Outcome,
+    /// When the entry was made, only kept when there's a TTL.
+    created: Option<Instant>,
+    /// The directories searched, with what they looked like then.
+    dirs: Vec<(PathBuf, DirState)>,
+}
+
+/// What a watched directory looks like.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DirState {
+    Missing,
+    Modified(SystemTime),
+    /// Its modification time can't be read, so changes to it can't be noticed.
+    Unknown,
+}
+
+#[derive(Clone)]
+enum Outcome {
+    All(Vec<PathBuf>),
+    Found(PathBuf),
+    FoundRelativeToCwd(PathBuf),
+    NotFound(SearchContext),
+}
+
+impl<TSys: Sys, F: NonFatalErrorHandler, C: CandidateFilter, K: Checker>
+    CachingResolver<TSys, F, C, K>
+{
+    pub(crate) fn new(
+        finder: Finder<TSys, C, K>,
+        path_list: PathListOption,
+        cwd: CwdOption,
+        nonfatal_error_handler: F,
+    ) -> Self {
+        CachingResolver {
+            path_list,
+            cwd,
+            path_list_edits: finder.path_list_edits,
+            path_extensions: finder.path_extensions,
+            refuse_relative_path_entries: finder.refuse_relative_path_entries,
+            ttl: None,
+            cache: Mutex::new(HashMap::new()),
+            nonfatal_error_handler: Mutex::new(nonfatal_error_handler),
+            filter: Mutex::new(finder.filter),
+            checker: finder.checker,
+            sys: finder.sys,
+        }
+    }
+
+    /// Forget results older than `ttl`, even when no directory changed. By default results are
+    /// kept until a directory changes or [`rehash`](CachingResolver::rehash) is called, and not
+    /// at all when a directory's modification time can't be read.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the first result for `binary_name`, like
+    /// [`WhichConfig::first_result`](crate::WhichConfig::first_result).
+    pub fn resolve<T: AsRef<OsStr>>(&self, binary_name: T) -> Result<PathBuf> {
+        match self.lookup(binary_name.as_ref(), false)? {
+            Outcome::Found(path) => Ok(path),
+            Outcome::FoundRelativeToCwd(path) => Err(Error::FoundRelativeToCwd(path)),
+            Outcome::NotFound(context) => Err(Error::CannotFindBinaryPath(context)),
+            Outcome::All(_) => unreachable!("only resolve_all caches every result"),
+        }
+    }
+
+    /// Returns every result for `binary_name`, like
+    /// [`WhichConfig::all_results`](crate::WhichConfig::all_results).
+    pub fn resolve_all<T: AsRef<OsStr>>(
+        &self,
+        binary_name: T,
+    ) -> Result<impl Iterator<Item = PathBuf>> {
+        match self.lookup(binary_name.as_ref(), true)? {
+            Outcome::All(paths) => Ok(paths.into_iter()),
+            _ => unreachable!("resolve_all caches every result"),
+        }
+    }
+
+    /// Forgets every result, like the shell's `hash -r`.
+    pub fn rehash(&self) {
+        self.lock_cache().clear();
+    }
+
+    fn lookup(&self, binary_name: &OsStr, all: bool) -> Result<Outcome> {
+        let key = Key {
+            binary_name: binary_name.to_os_string(),
+            all,
+            cwd: self.cwd.clone().resolve(&self.sys),
+            paths: self.path_list.clone().resolve(&self.sys),
+            path_extensions: path_extensions(&self.sys, self.path_extensions.as_ref()).into_owned(),
+        };
+        {
+            let mut cache = self.lock_cache();
+            if let Some(entry) = cache.get(&key) {
+                if self.is_fresh(entry) {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!("{} answered from the cache", binary_name.to_string_lossy());
+                    return Ok(entry.outcome.clone());
+                }
+                cache.remove(&key);
+            }
+        }
+
+        // Search without holding the lock, so other names can be looked up meanwhile.
+        let binary_name = PathBuf::from(binary_name);
+        let dirs = if key.cwd.is_some() && binary_name.has_separator() {
+            None
+        } else {
+            search_dirs(&self.sys, key.paths.as_deref(), &self.path_list_edits)
+        };
+        // Read the modification times before searching, so a change made during the search
+        // makes the entry stale instead of being missed.
+        let mut watched: Vec<_> = self
+            .watched_dirs(&binary_name, dirs.as_deref(), key.cwd.as_deref())
+            .into_iter()
+            .map(|dir| {
+                let state = self.dir_state(&dir);
+                (dir, state)
+            })
+            .collect();
+        let mut iter = self.find_iter(binary_name, dirs, &key)?;
+        let outcome = if all {
+            Outcome::All(iter.collect())
+        } else {
+            let outcome = match iter.first() {
+                Ok(path) => Outcome::Found(path),
+                Err(Error::FoundRelativeToCwd(path)) => Outcome::FoundRelativeToCwd(path),
+                Err(Error::CannotFindBinaryPath(context)) => Outcome::NotFound(context),
+                Err(e) => return Err(e),
+            };
+            // Only a new file in an earlier directory can change the first result, so later
+            // directories don't need to be checked on every hit.
+            if let Some(dir) = iter.last_dir() {
+                if let Some(i) = watched.iter().position(|(watched, _)| *watched == dir.dir) {
+                    watched.truncate(i + 1);
+                }
+            }
+            outcome
+        };
+        let entry = Entry {
+            outcome: outcome.clone(),
+            created: self.ttl.map(|_| Instant::now()),
+            dirs: watched,
+        };
+        self.lock_cache().insert(key, entry);
+        Ok(outcome)
+    }
+
+    fn find_iter(
+        &self,
+        binary_name: PathBuf,
+        dirs: Option<Vec<SearchDir>>,
+        key: &Key,
+    ) -> Result<WhichFindIterator<&TSys, Locked<'_, F>, Locked<'_, C>, &K>> {
+        Finder::new(&self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions.clone())
+            .filter(Locked(&self.filter))
+            .checker(&self.checker)
+            .find_in(
+                binary_name,
+                dirs,
+                Cow::Owned(key.path_extensions.clone()),
+                key.cwd.as_ref(),
+                Locked(&self.nonfatal_error_handler),
+            )
+    }
+
+    /// The directories whose contents decide the result of a query.
+    fn watched_dirs(
+        &self,
+        binary_name: &Path,
+        dirs: Option<&[SearchDir]>,
+        cwd: Option<&Path>,
+    ) -> Vec<PathBuf> {
+        match (dirs, cwd) {
+            (Some(dirs), _) => {
+                let mut watched = Vec::<PathBuf>::with_capacity(dirs.len());
+                for dir in dirs {
+                    if !watched.contains(&dir.dir) {
+                        watched.push(dir.dir.clone());
+                    }
+                }
+                watched
+            }
+            (None, Some(cwd)) => binary_name
+                .to_path_buf()
+                .to_absolute(cwd)
+                .parent()
+                .map(Path::to_path_buf)
+                .into_iter()
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+
+    fn is_fresh(&self, entry: &Entry) -> bool {
+        if let (Some(ttl), Some(created)) = (self.ttl, entry.created) {
+            if created.elapsed() >= ttl {
+                return false;
+            }
+        }
+        entry
+            .dirs
+            .iter()
+            .all(|(dir, then)| match (*then, self.dir_state(dir)) {
+                // Only the TTL can tell when the entry is out of date.
+                (DirState::Unknown, _) | (_, DirState::Unknown) => entry.created.is_some(),
+                (then, now) => then == now,
+            })
+    }
+
+    fn dir_state(&self, dir: &Path) -> DirState {
+        match self.sys.metadata(dir) {
+            Ok(metadata) => metadata
+                .modified()
+                .map_or(DirState::Unknown, DirState::Modified),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DirState::Missing,
+            Err(_) => DirState::Unknown,
+        }
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, HashMap<Key, Entry>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
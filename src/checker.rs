@@ -1,75 +1,143 @@
+use crate::explain::CandidateOutcome;
 use crate::sys::Sys;
 use crate::sys::SysMetadata;
 use crate::{NonFatalError, NonFatalErrorHandler};
 use std::path::Path;
 
-pub fn is_valid<F: NonFatalErrorHandler>(
+/// Decides whether a candidate path is a valid match.
+///
+/// [`DefaultChecker`] accepts executable regular files. Implement this trait to search for
+/// something else, e.g. files that are only readable, or executables built for a particular
+/// architecture.
+///
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use which::sys::{Sys, SysMetadata};
+/// use which::{CandidateOutcome, Checker};
+///
+/// /// Accepts any regular file, executable or not.
+/// struct AnyFile;
+///
+/// impl Checker for AnyFile {
+///     fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+///         match sys.metadata(path) {
+///             Ok(metadata) if metadata.is_file() => CandidateOutcome::Match,
+///             Ok(_) => CandidateOutcome::NotAFile,
+///             Err(e) => CandidateOutcome::Missing(e),
+///         }
+///     }
+/// }
+/// ```
+pub trait Checker {
+    /// Checks `path`, returning [`CandidateOutcome::Match`] if it's a valid match, or the reason
+    /// it was rejected otherwise.
+    fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome;
+}
+
+impl<T: Checker + ?Sized> Checker for &T {
+    fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+        (**self).check(sys, path)
+    }
+}
+
+/// The [`Checker`] used unless another one is set: accepts regular files that are executable.
+///
+/// On Windows, files with an extension are assumed to be executable, and symlinks are accepted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultChecker;
+
+impl Checker for DefaultChecker {
+    fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+        match exists(&sys, path) {
+            Ok(()) => is_executable(&sys, path),
+            Err(outcome) => outcome,
+        }
+    }
+}
+
+/// A [`Checker`] that accepts regular files whether or not they're executable, following
+/// symlinks. Used by [`WhichConfig::files`](crate::WhichConfig::files).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileChecker;
+
+impl Checker for FileChecker {
+    fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+        match sys.metadata(path) {
+            Ok(metadata) if metadata.is_file() => CandidateOutcome::Match,
+            Ok(_) => CandidateOutcome::NotAFile,
+            Err(e) => CandidateOutcome::Missing(e),
+        }
+    }
+}
+
+pub fn is_valid<K: Checker, F: NonFatalErrorHandler>(
+    checker: &K,
     sys: impl Sys,
     path: &Path,
     nonfatal_error_handler: &mut F,
 ) -> bool {
-    exists(&sys, path, nonfatal_error_handler) && is_executable(&sys, path, nonfatal_error_handler)
+    is_valid_outcome(checker.check(sys, path), nonfatal_error_handler)
 }
 
-fn is_executable<F: NonFatalErrorHandler>(
-    sys: impl Sys,
-    path: &Path,
+/// Like [`is_valid`], for a candidate that was already checked.
+pub fn is_valid_outcome<F: NonFatalErrorHandler>(
+    outcome: CandidateOutcome,
     nonfatal_error_handler: &mut F,
 ) -> bool {
+    match outcome {
+        CandidateOutcome::Match => true,
+        CandidateOutcome::Missing(e) | CandidateOutcome::NotExecutable(Some(e)) => {
+            nonfatal_error_handler.handle(NonFatalError::Io(e));
+            false
+        }
+        _ => false,
+    }
+}
+
+fn is_executable(sys: impl Sys, path: &Path) -> CandidateOutcome {
     if sys.is_windows() && path.extension().is_some() {
-        true
+        CandidateOutcome::Match
     } else {
-        let ret = sys
-            .is_valid_executable(path)
-            .map_err(|e| nonfatal_error_handler.handle(NonFatalError::Io(e)))
-            .unwrap_or(false);
+        let ret = sys.is_valid_executable(path);
         #[cfg(feature = "tracing")]
-        tracing::trace!("{} EXEC_OK = {ret}", path.display());
-        ret
+        tracing::trace!("{} EXEC_OK = {ret:?}", path.display());
+        match ret {
+            Ok(true) => CandidateOutcome::Match,
+            Ok(false) => CandidateOutcome::NotExecutable(None),
+            Err(e) => CandidateOutcome::NotExecutable(Some(e)),
+        }
     }
 }
 
-fn exists<F: NonFatalErrorHandler>(
-    sys: impl Sys,
-    path: &Path,
-    nonfatal_error_handler: &mut F,
-) -> bool {
-    {
-        if sys.is_windows() {
-            let ret = sys
-                .symlink_metadata(path)
-                .map(|metadata| {
-                    #[cfg(feature = "tracing")]
-                    tracing::trace!(
-                        "{} is_file() = {}, is_symlink() = {}",
-                        path.display(),
-                        metadata.is_file(),
-                        metadata.is_symlink()
-                    );
-                    metadata.is_file() || metadata.is_symlink()
-                })
-                .map_err(|e| {
-                    nonfatal_error_handler.handle(NonFatalError::Io(e));
-                })
-                .unwrap_or(false);
+fn exists(sys: impl Sys, path: &Path) -> Result<(), CandidateOutcome> {
+    let ret = if sys.is_windows() {
+        let ret = sys.symlink_metadata(path).map(|metadata| {
             #[cfg(feature = "tracing")]
             tracing::trace!(
-                "{} has_extension = {}, checker::exists() = {ret}",
+                "{} is_file() = {}, is_symlink() = {}",
                 path.display(),
-                path.extension().is_some()
+                metadata.is_file(),
+                metadata.is_symlink()
             );
-            ret
-        } else {
-            let ret = sys.metadata(path).map(|metadata| metadata.is_file());
-            #[cfg(feature = "tracing")]
-            tracing::trace!("{} is_file() = {ret:?}", path.display());
-            match ret {
-                Ok(ret) => ret,
-                Err(e) => {
-                    nonfatal_error_handler.handle(NonFatalError::Io(e));
-                    false
-                }
-            }
-        }
+            metadata.is_file() || metadata.is_symlink()
+        });
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            "{} has_extension = {}, checker::exists() = {ret:?}",
+            path.display(),
+            path.extension().is_some()
+        );
+        ret
+    } else {
+        let ret = sys.metadata(path).map(|metadata| metadata.is_file());
+        #[cfg(feature = "tracing")]
+        tracing::trace!("{} is_file() = {ret:?}", path.display());
+        ret
+    };
+    match ret {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(CandidateOutcome::NotAFile),
+        Err(e) => Err(CandidateOutcome::Missing(e)),
     }
 }
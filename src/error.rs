@@ -1,28 +1,121 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
 use std::{fmt, io};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// An executable binary with that name was not found
-    CannotFindBinaryPath,
+    CannotFindBinaryPath(SearchContext),
     /// There was nowhere to search and the provided name wasn't an absolute path
-    CannotGetCurrentDirAndPathListEmpty,
+    CannotGetCurrentDirAndPathListEmpty(SearchContext),
     /// Failed to canonicalize the path found
-    CannotCanonicalize,
+    CannotCanonicalize {
+        /// The path that was found.
+        path: PathBuf,
+        /// The error returned while canonicalizing it.
+        source: io::Error,
+    },
+    /// The first match was found through an empty or relative entry in the path list, which was
+    /// refused by [`WhichConfig::refuse_relative_path_entries`](crate::WhichConfig::refuse_relative_path_entries).
+    FoundRelativeToCwd(PathBuf),
+    /// Failed to read the ELF object given to
+    /// [`LibraryConfig::object`](crate::LibraryConfig::object)
+    CannotReadObject {
+        /// The path of the object.
+        path: PathBuf,
+        /// The error returned while reading or parsing it.
+        source: io::Error,
+    },
+    /// A [`Query`](crate::Query) had an empty scope or pattern.
+    InvalidQuery(String),
+    /// Failed to save a [`CommandIndex`](crate::CommandIndex).
+    CannotWriteIndex {
+        /// Where the index was to be saved, or `None` if no cache directory could be found.
+        path: Option<PathBuf>,
+        /// The error returned while writing it.
+        source: io::Error,
+    },
 }
 
-impl std::error::Error for Error {}
+/// Describes the search that failed to produce a result.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SearchContext {
+    /// The name, regex or glob pattern that was searched for, or `None` if it isn't known, e.g.
+    /// for `which_re`.
+    pub query: Option<OsString>,
+    /// The directories from the path list that were searched, after tilde expansion.
+    pub paths: Vec<PathBuf>,
+    /// The directory the query was resolved against, if it was resolved relative to one.
+    pub cwd: Option<PathBuf>,
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CannotCanonicalize { source, .. }
+            | Error::CannotReadObject { source, .. }
+            | Error::CannotWriteIndex { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::CannotFindBinaryPath => write!(f, "cannot find binary path"),
-            Error::CannotGetCurrentDirAndPathListEmpty => write!(
+            Error::CannotFindBinaryPath(context) => {
+                match &context.query {
+                    Some(query) => write!(f, "cannot find binary `{}`", query.to_string_lossy())?,
+                    None => write!(f, "cannot find binary path")?,
+                }
+                if let Some(cwd) = &context.cwd {
+                    write!(f, " relative to {}", cwd.display())?;
+                }
+                if !context.paths.is_empty() {
+                    write!(f, " in any of the searched directories: ")?;
+                    for (i, path) in context.paths.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", path.display())?;
+                    }
+                }
+                Ok(())
+            }
+            Error::CannotGetCurrentDirAndPathListEmpty(context) => match &context.query {
+                Some(query) => write!(
+                    f,
+                    "no path to search and `{}` is not an absolute path",
+                    query.to_string_lossy()
+                ),
+                None => write!(
+                    f,
+                    "no path to search and provided name is not an absolute path"
+                ),
+            },
+            Error::CannotCanonicalize { path, source } => {
+                write!(f, "cannot canonicalize {}: {source}", path.display())
+            }
+            Error::FoundRelativeToCwd(path) => write!(
                 f,
-                "no path to search and provided name is not an absolute path"
+                "refusing to use {}, found relative to the current directory",
+                path.display()
             ),
-            Error::CannotCanonicalize => write!(f, "cannot canonicalize path"),
+            Error::CannotReadObject { path, source } => {
+                write!(f, "cannot read ELF object {}: {source}", path.display())
+            }
+            Error::InvalidQuery(query) => write!(f, "invalid query `{query}`"),
+            Error::CannotWriteIndex {
+                path: Some(path),
+                source,
+            } => write!(f, "cannot write command index {}: {source}", path.display()),
+            Error::CannotWriteIndex { path: None, source } => {
+                write!(f, "cannot write command index: {source}")
+            }
         }
     }
 }
@@ -35,6 +128,12 @@ pub enum NonFatalError {
     /// and the query did not specify a file extension. This is technically legal but probably not
     /// intentional.
     PathExtNotPopulated,
+    /// A match was skipped because it was found through an empty or relative entry in the path
+    /// list, which was refused by [`WhichConfig::refuse_relative_path_entries`](crate::WhichConfig::refuse_relative_path_entries).
+    FoundRelativeToCwd(PathBuf),
+    /// A match was skipped because it was rejected by
+    /// [`WhichConfig::filter`](crate::WhichConfig::filter).
+    RejectedByFilter(PathBuf),
 }
 
 impl std::error::Error for NonFatalError {}
@@ -44,6 +143,12 @@ impl fmt::Display for NonFatalError {
         match self {
             Self::Io(e) => write!(f, "{e}"),
             Self::PathExtNotPopulated => write!(f, "PATHEXT environment variable is not populated, and the query did not specify a file extension"),
+            Self::FoundRelativeToCwd(path) => write!(
+                f,
+                "skipped {}, found relative to the current directory",
+                path.display()
+            ),
+            Self::RejectedByFilter(path) => write!(f, "skipped {}, rejected by filter", path.display()),
         }
     }
 }
@@ -0,0 +1,73 @@
+use std::io;
+use std::path::PathBuf;
+
+/// A report of every candidate path probed while answering a query.
+///
+/// Produced by [`WhichConfig::explain`](crate::WhichConfig::explain).
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Explanation {
+    /// Every candidate that was probed, in the order they were probed.
+    pub candidates: Vec<ExplainedCandidate>,
+    /// On Windows, the `PATHEXT` environment variable had no usable extensions and the query
+    /// did not specify one. See [`NonFatalError::PathExtNotPopulated`](crate::NonFatalError::PathExtNotPopulated).
+    pub path_ext_not_populated: bool,
+}
+
+impl Explanation {
+    /// Returns the candidates that passed every check, in the order `which` would return them.
+    pub fn matches(&self) -> impl Iterator<Item = &ExplainedCandidate> {
+        self.candidates.iter().filter(|c| c.outcome.is_match())
+    }
+}
+
+/// A single candidate path probed during a query, along with why it was accepted or rejected.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ExplainedCandidate {
+    /// The index of the entry in the path list that produced this candidate, or `None` if the
+    /// query contained a path separator and was resolved against the current working directory.
+    pub entry_index: Option<usize>,
+    /// The directory that was searched, after tilde expansion.
+    pub directory: PathBuf,
+    /// The path list entry started with `~`, but the user's home directory couldn't be found,
+    /// so it was searched without expansion.
+    pub tilde_expansion_failed: bool,
+    /// The full path that was probed.
+    pub path: PathBuf,
+    /// The extension from `PATHEXT` appended to the query to produce this candidate, if any.
+    pub extension: Option<String>,
+    /// The outcome of checking the candidate.
+    pub outcome: CandidateOutcome,
+}
+
+/// The outcome of checking whether a candidate path is a valid match.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CandidateOutcome {
+    /// The candidate passed every check.
+    Match,
+    /// The candidate's metadata couldn't be read, usually because it doesn't exist.
+    Missing(io::Error),
+    /// The candidate exists, but isn't a regular file.
+    NotAFile,
+    /// The candidate is a file, but isn't executable. Contains the error reported by the
+    /// system, e.g. from `access(X_OK)`, if there was one.
+    NotExecutable(Option<io::Error>),
+    /// The candidate was rejected by a custom [`Checker`](crate::Checker), for the given reason.
+    Rejected(String),
+    /// The candidate passed every check, but was rejected by
+    /// [`WhichConfig::filter`](crate::WhichConfig::filter).
+    Filtered,
+    /// The candidate passed every check, but was found through an empty or relative entry of
+    /// the path list, which was refused by
+    /// [`WhichConfig::refuse_relative_path_entries`](crate::WhichConfig::refuse_relative_path_entries).
+    RelativePathEntry,
+}
+
+impl CandidateOutcome {
+    /// Returns `true` if the candidate passed every check.
+    pub fn is_match(&self) -> bool {
+        matches!(self, CandidateOutcome::Match)
+    }
+}
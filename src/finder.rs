@@ -1,20 +1,21 @@
-use crate::checker::is_valid;
-use crate::helper::has_executable_extension;
+use crate::checker::{is_valid, is_valid_outcome, Checker, DefaultChecker};
+use crate::explain::{CandidateOutcome, ExplainedCandidate, Explanation};
+use crate::helper::{has_executable_extension, wildcard_match};
+use crate::parallel::{Pool, Spawner};
+use crate::scan::command_name;
 use crate::sys::Sys;
 use crate::sys::SysReadDirEntry;
-use crate::{error::*, NonFatalErrorHandler};
 #[cfg(feature = "regex")]
-use regex::Regex;
-#[cfg(feature = "regex")]
-use std::borrow::Borrow;
+use crate::RegexLike;
+use crate::{error::*, CandidateFilter, ExcludePath, NonFatalErrorHandler, Noop, PathExtensions};
 use std::borrow::Cow;
-use std::ffi::OsStr;
-#[cfg(feature = "regex")]
+use std::ffi::{OsStr, OsString};
 use std::io;
+use std::iter;
 use std::path::{Component, Path, PathBuf};
 use std::vec;
 
-trait PathExt {
+pub(crate) trait PathExt {
     fn has_separator(&self) -> bool;
 
     fn to_absolute<P>(self, cwd: P) -> PathBuf
@@ -44,13 +45,79 @@ impl PathExt for PathBuf {
     }
 }
 
-pub struct Finder<TSys: Sys> {
-    sys: TSys,
+pub struct Finder<TSys: Sys, P = Noop, K = DefaultChecker> {
+    pub(crate) sys: TSys,
+    pub(crate) refuse_relative_path_entries: bool,
+    pub(crate) path_extensions: Option<PathExtensions>,
+    pub(crate) path_list_edits: PathListEdits,
+    pub(crate) filter: P,
+    pub(crate) checker: K,
+    pub(crate) spawner: Option<Spawner<TSys, K>>,
 }
 
 impl<TSys: Sys> Finder<TSys> {
     pub fn new(sys: TSys) -> Self {
-        Finder { sys }
+        Finder {
+            sys,
+            refuse_relative_path_entries: false,
+            path_extensions: None,
+            path_list_edits: PathListEdits::default(),
+            filter: Noop,
+            checker: DefaultChecker,
+            spawner: None,
+        }
+    }
+}
+
+impl<TSys: Sys, P: CandidateFilter, K: Checker> Finder<TSys, P, K> {
+    /// Refuse matches found through empty or relative entries in the path list.
+    pub fn refuse_relative_path_entries(mut self, refuse: bool) -> Self {
+        self.refuse_relative_path_entries = refuse;
+        self
+    }
+
+    /// Change the extensions tried on Windows, rather than only using `PATHEXT`.
+    pub fn path_extensions(mut self, path_extensions: Option<PathExtensions>) -> Self {
+        self.path_extensions = path_extensions;
+        self
+    }
+
+    /// Add to or remove from the directories of the path list.
+    pub fn path_list_edits(mut self, path_list_edits: PathListEdits) -> Self {
+        self.path_list_edits = path_list_edits;
+        self
+    }
+
+    /// Reject matches the filter doesn't accept, and keep searching.
+    pub fn filter<NewP: CandidateFilter>(self, filter: NewP) -> Finder<TSys, NewP, K> {
+        Finder {
+            sys: self.sys,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            path_extensions: self.path_extensions,
+            path_list_edits: self.path_list_edits,
+            filter,
+            checker: self.checker,
+            spawner: self.spawner,
+        }
+    }
+
+    /// Decide whether a candidate is a match with `checker` instead of [`DefaultChecker`].
+    pub fn checker<NewK: Checker>(self, checker: NewK) -> Finder<TSys, P, NewK> {
+        Finder {
+            sys: self.sys,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            path_extensions: self.path_extensions,
+            path_list_edits: self.path_list_edits,
+            filter: self.filter,
+            checker,
+            spawner: None,
+        }
+    }
+
+    /// Check candidates on the threads started by `spawner`, if any.
+    pub fn spawner(mut self, spawner: Option<Spawner<TSys, K>>) -> Self {
+        self.spawner = spawner;
+        self
     }
 
     pub fn find<'a, T, U, V, F: NonFatalErrorHandler + 'a>(
@@ -62,9 +129,84 @@ impl<TSys: Sys> Finder<TSys> {
     ) -> Result<impl Iterator<Item = PathBuf> + 'a>
     where
         TSys: 'a,
+        P: 'a,
+        K: 'a,
         T: AsRef<OsStr>,
         U: AsRef<OsStr>,
         V: AsRef<Path> + 'a,
+    {
+        let ret = self.find_iter(binary_name, paths, cwd, nonfatal_error_handler)?;
+        #[cfg(feature = "tracing")]
+        let ret = ret.inspect(|p| {
+            tracing::debug!("found path {}", p.display());
+        });
+        Ok(ret)
+    }
+
+    pub fn find_first<T, U, V, F: NonFatalErrorHandler>(
+        self,
+        binary_name: T,
+        paths: Option<U>,
+        cwd: Option<V>,
+        nonfatal_error_handler: F,
+    ) -> Result<PathBuf>
+    where
+        T: AsRef<OsStr>,
+        U: AsRef<OsStr>,
+        V: AsRef<Path>,
+    {
+        self.find_iter(binary_name, paths, cwd, nonfatal_error_handler)?
+            .first()
+    }
+
+    /// Finds the first match, along with an iterator over the remaining matches.
+    pub fn find_first_and_rest<'a, T, U, V, F: NonFatalErrorHandler + 'a>(
+        self,
+        binary_name: T,
+        paths: Option<U>,
+        cwd: Option<V>,
+        nonfatal_error_handler: F,
+    ) -> Result<(PathBuf, impl Iterator<Item = PathBuf> + 'a)>
+    where
+        TSys: 'a,
+        P: 'a,
+        K: 'a,
+        T: AsRef<OsStr>,
+        U: AsRef<OsStr>,
+        V: AsRef<Path>,
+    {
+        let mut iter = self.find_iter(binary_name, paths, cwd, nonfatal_error_handler)?;
+        let first = iter.first()?;
+        Ok((first, iter))
+    }
+
+    pub fn explain<T, U, V, F: NonFatalErrorHandler>(
+        self,
+        binary_name: T,
+        paths: Option<U>,
+        cwd: Option<V>,
+        nonfatal_error_handler: F,
+    ) -> Result<Explanation>
+    where
+        T: AsRef<OsStr>,
+        U: AsRef<OsStr>,
+        V: AsRef<Path>,
+    {
+        self.find_iter(binary_name, paths, cwd, nonfatal_error_handler)
+            .map(WhichFindIterator::explain)
+    }
+
+    fn find_iter<T, U, V, F: NonFatalErrorHandler>(
+        self,
+        binary_name: T,
+        paths: Option<U>,
+        cwd: Option<V>,
+        nonfatal_error_handler: F,
+    ) -> Result<WhichFindIterator<TSys, F, P, K>>
+    where
+        T: AsRef<OsStr>,
+        U: AsRef<OsStr>,
+        V: AsRef<Path>,
     {
         let path = PathBuf::from(&binary_name);
 
@@ -76,139 +218,551 @@ impl<TSys: Sys> Finder<TSys> {
             cwd.as_ref().map(|p| p.as_ref().display())
         );
 
-        let ret = match cwd {
-            Some(cwd) if path.has_separator() => {
-                WhichFindIterator::new_cwd(path, cwd.as_ref(), self.sys, nonfatal_error_handler)
-            }
+        // A query with a separator is resolved against the cwd, without the path list.
+        let dirs = if cwd.is_some() && path.has_separator() {
+            None
+        } else {
+            search_dirs(
+                &self.sys,
+                paths.as_ref().map(AsRef::as_ref),
+                &self.path_list_edits,
+            )
+        };
+        let path_extensions = path_extensions(&self.sys, self.path_extensions.as_ref());
+        self.find_in(path, dirs, path_extensions, cwd, nonfatal_error_handler)
+    }
+
+    /// Searches for `binary_name` in directories that were already split from the path list and
+    /// expanded, trying `path_extensions` on Windows. `dirs` is `None` if there was nowhere to
+    /// search.
+    pub(crate) fn find_in<V, F: NonFatalErrorHandler>(
+        self,
+        binary_name: PathBuf,
+        dirs: Option<Vec<SearchDir>>,
+        path_extensions: Cow<'static, [String]>,
+        cwd: Option<V>,
+        nonfatal_error_handler: F,
+    ) -> Result<WhichFindIterator<TSys, F, P, K>>
+    where
+        V: AsRef<Path>,
+    {
+        match cwd {
+            Some(cwd) if binary_name.has_separator() => Ok(WhichFindIterator::new_cwd(
+                binary_name,
+                cwd.as_ref(),
+                path_extensions,
+                self,
+                nonfatal_error_handler,
+            )),
             _ => {
                 #[cfg(feature = "tracing")]
                 tracing::trace!(
                     "{} has no path seperators, so only paths in PATH environment variable will be searched.",
-                    path.display()
+                    binary_name.display()
                 );
                 // Search binary in PATHs(defined in environment variable).
-                let paths = paths.ok_or(Error::CannotGetCurrentDirAndPathListEmpty)?;
-                let paths = self.sys.env_split_paths(paths.as_ref());
-                if paths.is_empty() {
-                    return Err(Error::CannotGetCurrentDirAndPathListEmpty);
-                }
-                WhichFindIterator::new_paths(path, paths, self.sys, nonfatal_error_handler)
+                let dirs = dirs.ok_or_else(|| {
+                    Error::CannotGetCurrentDirAndPathListEmpty(SearchContext {
+                        query: Some(binary_name.clone().into_os_string()),
+                        ..Default::default()
+                    })
+                })?;
+                Ok(WhichFindIterator::new_paths(
+                    binary_name,
+                    dirs,
+                    path_extensions,
+                    self,
+                    nonfatal_error_handler,
+                ))
             }
-        };
-        #[cfg(feature = "tracing")]
-        let ret = ret.inspect(|p| {
-            tracing::debug!("found path {}", p.display());
-        });
-        Ok(ret)
+        }
     }
 
-    #[cfg(feature = "regex")]
-    pub fn find_re<T, F: NonFatalErrorHandler>(
+    /// Finds every candidate whose name, or full path, is accepted by `matcher`, for regex and
+    /// glob searches.
+    pub fn find_matching<M: NameMatcher, T, U, F: NonFatalErrorHandler>(
         self,
-        binary_regex: impl std::borrow::Borrow<Regex>,
+        matcher: M,
+        query: Option<OsString>,
         paths: Option<T>,
+        cwd: Option<U>,
+        options: MatchOptions,
         nonfatal_error_handler: F,
-    ) -> Result<impl Iterator<Item = PathBuf>>
+    ) -> Result<WhichFindMatchIter<TSys, M, F, P, K>>
     where
         T: AsRef<OsStr>,
+        U: AsRef<Path>,
     {
-        WhichFindRegexIter::new(self.sys, paths, binary_regex, nonfatal_error_handler)
+        // The cwd is searched before the path list, like a shell finding a script in the
+        // project directory before falling back to installed commands.
+        let mut dirs: Vec<SearchDir> = cwd
+            .iter()
+            .map(|cwd| SearchDir {
+                index: None,
+                dir: cwd.as_ref().to_path_buf(),
+                tilde_expansion_failed: false,
+            })
+            .collect();
+        match search_dirs(
+            &self.sys,
+            paths.as_ref().map(AsRef::as_ref),
+            &self.path_list_edits,
+        ) {
+            Some(path_dirs) => dirs.extend(path_dirs),
+            None if dirs.is_empty() => {
+                return Err(Error::CannotGetCurrentDirAndPathListEmpty(SearchContext {
+                    query,
+                    ..Default::default()
+                }))
+            }
+            None => {}
+        }
+        let path_extensions = path_extensions(&self.sys, self.path_extensions.as_ref());
+        Ok(WhichFindMatchIter {
+            sys: self.sys,
+            matcher,
+            query,
+            options,
+            path_extensions,
+            dirs,
+            next_dir: 0,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            filter: self.filter,
+            checker: self.checker,
+            nonfatal_error_handler,
+            current_read_dir_iter: None,
+            current_dir_is_relative: false,
+        })
+    }
+}
+
+/// A directory searched for candidates.
+#[derive(Clone)]
+pub(crate) struct SearchDir {
+    /// Index of the entry in the path list, or `None` when resolving a path against the cwd.
+    pub index: Option<usize>,
+    /// The directory, after tilde expansion.
+    pub dir: PathBuf,
+    /// The entry started with `~`, but the user's home directory couldn't be found.
+    pub tilde_expansion_failed: bool,
+}
+
+/// Directories added to or removed from the path list by
+/// [`WhichConfig::prepend_paths`](crate::WhichConfig::prepend_paths),
+/// [`WhichConfig::append_paths`](crate::WhichConfig::append_paths) and
+/// [`WhichConfig::exclude_path`](crate::WhichConfig::exclude_path), and the directories below
+/// each of them searched instead, set by [`WhichConfig::subpaths`](crate::WhichConfig::subpaths).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PathListEdits {
+    pub prepend: Vec<PathBuf>,
+    pub append: Vec<PathBuf>,
+    pub exclude: Vec<ExcludePath>,
+    pub subpaths: Vec<PathBuf>,
+}
+
+/// Splits a path list, applies `edits` to it and turns its entries into the directories that
+/// should be searched. Returns `None` if there's no path list and nothing was added to it.
+pub(crate) fn search_dirs<TSys: Sys>(
+    sys: TSys,
+    paths: Option<&OsStr>,
+    edits: &PathListEdits,
+) -> Option<Vec<SearchDir>> {
+    let paths = paths.map(|p| sys.env_split_paths(p)).unwrap_or_default();
+    if paths.is_empty() && edits.prepend.is_empty() && edits.append.is_empty() {
+        return None;
+    }
+    let paths = edits
+        .prepend
+        .iter()
+        .chain(&paths)
+        .chain(&edits.append)
+        .enumerate();
+
+    // PowerShell Get-Command omits empty entries in PATH string, unix `which` command does not.
+    // Emulate OS specific behavior here.
+    #[cfg(target_os = "windows")]
+    let paths = paths.filter(|(_, p)| !p.as_os_str().is_empty());
+
+    let exclude = Exclusions::new(&sys, &edits.exclude);
+    let dirs = paths
+        .map(|(index, p)| {
+            let dir = tilde_expansion(&sys, p);
+            SearchDir {
+                index: Some(index),
+                tilde_expansion_failed: matches!(dir, Cow::Borrowed(_)) && has_tilde(p),
+                dir: dir.into_owned(),
+            }
+        })
+        .filter(|d| {
+            let excluded = exclude.matches(&d.dir);
+            #[cfg(feature = "tracing")]
+            if excluded {
+                tracing::trace!("excluding {} from the search", d.dir.display());
+            }
+            !excluded
+        });
+    if edits.subpaths.is_empty() {
+        return Some(dirs.collect());
+    }
+    Some(
+        dirs.flat_map(|d| {
+            edits.subpaths.iter().map(move |subpath| SearchDir {
+                index: d.index,
+                dir: d.dir.join(subpath),
+                tilde_expansion_failed: d.tilde_expansion_failed,
+            })
+        })
+        .collect(),
+    )
+}
+
+/// [`ExcludePath`]s prepared for matching against many directories.
+struct Exclusions<'a, TSys: Sys> {
+    sys: &'a TSys,
+    /// Each excluded path after tilde expansion, and canonicalization for
+    /// [`ExcludePath::Canonical`]. Canonical paths that can't be resolved are left out.
+    paths: Vec<(&'a ExcludePath, PathBuf)>,
+}
+
+impl<'a, TSys: Sys> Exclusions<'a, TSys> {
+    fn new(sys: &'a TSys, exclude: &'a [ExcludePath]) -> Self {
+        let paths = exclude
+            .iter()
+            .filter_map(|e| {
+                let path = match e {
+                    ExcludePath::Exact(p) | ExcludePath::Prefix(p) => {
+                        tilde_expansion(sys, p).into_owned()
+                    }
+                    ExcludePath::Canonical(p) => sys.canonicalize(&tilde_expansion(sys, p)).ok()?,
+                };
+                Some((e, path))
+            })
+            .collect();
+        Self { sys, paths }
+    }
+
+    fn matches(&self, dir: &Path) -> bool {
+        if self.paths.is_empty() {
+            return false;
+        }
+        let mut canonical = None;
+        self.paths.iter().any(|(e, path)| match e {
+            ExcludePath::Exact(_) => same_path(self.sys, dir, path),
+            ExcludePath::Prefix(_) => {
+                if self.sys.is_windows() {
+                    let (dir, path) = (lowercase(dir), lowercase(path));
+                    dir.starts_with(path)
+                } else {
+                    dir.starts_with(path)
+                }
+            }
+            ExcludePath::Canonical(_) => canonical
+                .get_or_insert_with(|| self.sys.canonicalize(dir).ok())
+                .as_ref()
+                .is_some_and(|dir| dir == path),
+        })
     }
 }
 
-struct WhichFindIterator<TSys: Sys, F: NonFatalErrorHandler> {
+/// Compares paths component by component, ignoring case on Windows.
+fn same_path<TSys: Sys>(sys: TSys, a: &Path, b: &Path) -> bool {
+    if sys.is_windows() {
+        lowercase(a) == lowercase(b)
+    } else {
+        a == b
+    }
+}
+
+fn lowercase(p: &Path) -> PathBuf {
+    PathBuf::from(p.to_string_lossy().to_lowercase())
+}
+
+pub(crate) struct WhichFindIterator<
+    TSys: Sys,
+    F: NonFatalErrorHandler,
+    P: CandidateFilter,
+    K: Checker,
+> {
     sys: TSys,
+    binary_name: PathBuf,
+    /// The directory a query containing a separator was resolved against.
+    cwd: Option<PathBuf>,
+    dirs: Vec<SearchDir>,
     paths: PathsIter<vec::IntoIter<PathBuf>>,
+    path_ext_not_populated: bool,
+    refuse_relative_path_entries: bool,
+    filter: P,
+    checker: K,
     nonfatal_error_handler: F,
+    spawner: Option<Spawner<TSys, K>>,
+    /// The candidates checked ahead on other threads, in parallel mode.
+    ahead: Option<Ahead>,
+    /// Index of the [`SearchDir`] the last match was found in.
+    last_dir: Option<usize>,
+}
+
+/// Every candidate of a parallel search, and the threads checking them.
+struct Ahead {
+    candidates: Vec<Candidate>,
+    pool: Pool,
+    /// Index of the next candidate to return.
+    position: usize,
 }
 
-impl<TSys: Sys, F: NonFatalErrorHandler> WhichFindIterator<TSys, F> {
+impl<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>
+    WhichFindIterator<TSys, F, P, K>
+{
     pub fn new_cwd(
         binary_name: PathBuf,
         cwd: &Path,
-        sys: TSys,
-        mut nonfatal_error_handler: F,
+        path_extensions: Cow<'static, [String]>,
+        finder: Finder<TSys, P, K>,
+        nonfatal_error_handler: F,
     ) -> Self {
-        let path_extensions = if sys.is_windows() {
-            sys.env_windows_path_ext()
-        } else {
-            Cow::Borrowed(Default::default())
+        let path = binary_name.clone().to_absolute(cwd);
+        let dir = SearchDir {
+            index: None,
+            dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            tilde_expansion_failed: false,
         };
-        if sys.is_windows() && path_extensions.is_empty() && binary_name.extension().is_none() {
-            nonfatal_error_handler.handle(NonFatalError::PathExtNotPopulated);
-        }
-        Self {
-            sys,
-            paths: PathsIter {
-                paths: vec![binary_name.to_absolute(cwd)].into_iter(),
-                current_path_with_index: None,
-                path_extensions,
-            },
+        let mut iter = Self::new(
+            binary_name,
+            vec![dir],
+            vec![path],
+            path_extensions,
+            finder,
             nonfatal_error_handler,
-        }
+        );
+        iter.cwd = Some(cwd.to_path_buf());
+        iter
     }
 
     pub fn new_paths(
         binary_name: PathBuf,
+        dirs: Vec<SearchDir>,
+        path_extensions: Cow<'static, [String]>,
+        finder: Finder<TSys, P, K>,
+        nonfatal_error_handler: F,
+    ) -> Self {
+        let paths = dirs.iter().map(|d| d.dir.join(&binary_name)).collect();
+
+        Self::new(
+            binary_name,
+            dirs,
+            paths,
+            path_extensions,
+            finder,
+            nonfatal_error_handler,
+        )
+    }
+
+    fn new(
+        binary_name: PathBuf,
+        dirs: Vec<SearchDir>,
         paths: Vec<PathBuf>,
-        sys: TSys,
+        path_extensions: Cow<'static, [String]>,
+        finder: Finder<TSys, P, K>,
         mut nonfatal_error_handler: F,
     ) -> Self {
-        let path_extensions = if sys.is_windows() {
-            sys.env_windows_path_ext()
-        } else {
-            Cow::Borrowed(Default::default())
-        };
-        if sys.is_windows() && path_extensions.is_empty() && binary_name.extension().is_none() {
+        // Emptying the list on purpose doesn't deserve a warning.
+        let path_ext_not_populated = finder.sys.is_windows()
+            && path_extensions.is_empty()
+            && binary_name.extension().is_none()
+            && !matches!(
+                finder.path_extensions,
+                Some(PathExtensions::Clear | PathExtensions::Replace(_))
+            );
+        if path_ext_not_populated {
             nonfatal_error_handler.handle(NonFatalError::PathExtNotPopulated);
         }
 
-        let paths = paths.iter();
-
-        // PowerShell Get-Command omits empty entries in PATH string, unix `which` command does not.
-        // Emulate OS specific behavior here.
-        #[cfg(target_os = "windows")]
-        let paths = paths.filter(|p| !p.as_os_str().is_empty());
-
-        let paths = paths
-            .map(|p| tilde_expansion(&sys, p).join(&binary_name))
-            .collect::<Vec<_>>();
-
         Self {
-            sys,
+            sys: finder.sys,
+            binary_name,
+            cwd: None,
+            dirs,
             paths: PathsIter {
-                paths: paths.into_iter(),
+                paths: paths.into_iter().enumerate(),
                 current_path_with_index: None,
                 path_extensions,
             },
+            path_ext_not_populated,
+            refuse_relative_path_entries: finder.refuse_relative_path_entries,
+            filter: finder.filter,
+            checker: finder.checker,
             nonfatal_error_handler,
+            spawner: finder.spawner,
+            ahead: None,
+            last_dir: None,
+        }
+    }
+
+    /// The directory the last match was found in, if there was one.
+    pub(crate) fn last_dir(&self) -> Option<&SearchDir> {
+        self.last_dir.map(|i| &self.dirs[i])
+    }
+
+    /// Returns the first match, or an error describing the search if there isn't one.
+    pub(crate) fn first(&mut self) -> Result<PathBuf> {
+        match self.next_checked() {
+            Some(Ok(path)) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("found path {}", path.display());
+                Ok(path)
+            }
+            Some(Err(path)) => Err(Error::FoundRelativeToCwd(path)),
+            None => Err(Error::CannotFindBinaryPath(SearchContext {
+                query: Some(self.binary_name.clone().into_os_string()),
+                paths: self
+                    .dirs
+                    .iter()
+                    .filter(|d| d.index.is_some())
+                    .map(|d| d.dir.clone())
+                    .collect(),
+                cwd: self.cwd.clone(),
+            })),
+        }
+    }
+
+    /// Returns the next match, or `Err` with the match if it was found through an empty or
+    /// relative path list entry and those are being refused.
+    fn next_checked(&mut self) -> Option<std::result::Result<PathBuf, PathBuf>> {
+        while let Some((candidate, outcome)) = self.next_probed() {
+            if is_valid_outcome(outcome, &mut self.nonfatal_error_handler) {
+                let path =
+                    correct_casing(&self.sys, candidate.path, &mut self.nonfatal_error_handler);
+                if !self.filter.accept(&path) {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("filter rejected {}", path.display());
+                    self.nonfatal_error_handler
+                        .handle(NonFatalError::RejectedByFilter(path));
+                    continue;
+                }
+                self.last_dir = Some(candidate.dir);
+                let dir = &self.dirs[candidate.dir];
+                if self.refuse_relative_path_entries && dir.index.is_some() && dir.dir.is_relative()
+                {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        "refusing {} as it was found relative to the current directory",
+                        path.display()
+                    );
+                    return Some(Err(path));
+                }
+                return Some(Ok(path));
+            }
+        }
+        None
+    }
+
+    /// Returns the next candidate along with the outcome of checking it.
+    fn next_probed(&mut self) -> Option<(Candidate, CandidateOutcome)> {
+        let Some(spawner) = self.spawner else {
+            let candidate = self.paths.next()?;
+            let outcome = self.checker.check(&self.sys, &candidate.path);
+            return Some((candidate, outcome));
+        };
+        let (paths, sys, checker) = (&mut self.paths, &self.sys, &self.checker);
+        let ahead = self.ahead.get_or_insert_with(|| {
+            let candidates: Vec<_> = paths.collect();
+            let pool = spawner.start(
+                sys,
+                checker,
+                candidates.iter().map(|c| c.path.clone()).collect(),
+            );
+            Ahead {
+                candidates,
+                pool,
+                position: 0,
+            }
+        });
+        let position = ahead.position;
+        if position == ahead.candidates.len() {
+            return None;
+        }
+        ahead.position += 1;
+        let candidate = &mut ahead.candidates[position];
+        let candidate = Candidate {
+            dir: candidate.dir,
+            path: std::mem::take(&mut candidate.path),
+            extension: candidate.extension,
+        };
+        // Without threads left to do it, the candidate is checked here.
+        let outcome = match ahead.pool.take(position) {
+            Some(outcome) => outcome,
+            None => self.checker.check(&self.sys, &candidate.path),
+        };
+        Some((candidate, outcome))
+    }
+
+    /// Probes every remaining candidate, recording why each was accepted or rejected.
+    fn explain(mut self) -> Explanation {
+        let mut candidates = Vec::new();
+        while let Some(candidate) = self.paths.next() {
+            let dir = &self.dirs[candidate.dir];
+            let outcome = match self.checker.check(&self.sys, &candidate.path) {
+                CandidateOutcome::Match if !self.filter.accept(&candidate.path) => {
+                    CandidateOutcome::Filtered
+                }
+                CandidateOutcome::Match
+                    if self.refuse_relative_path_entries
+                        && dir.index.is_some()
+                        && dir.dir.is_relative() =>
+                {
+                    CandidateOutcome::RelativePathEntry
+                }
+                outcome => outcome,
+            };
+            candidates.push(ExplainedCandidate {
+                entry_index: dir.index,
+                directory: dir.dir.clone(),
+                tilde_expansion_failed: dir.tilde_expansion_failed,
+                extension: candidate
+                    .extension
+                    .map(|i| self.paths.path_extensions[i].clone()),
+                path: candidate.path,
+                outcome,
+            });
+        }
+        Explanation {
+            candidates,
+            path_ext_not_populated: self.path_ext_not_populated,
         }
     }
 }
 
-impl<TSys: Sys, F: NonFatalErrorHandler> Iterator for WhichFindIterator<TSys, F> {
+impl<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker> Iterator
+    for WhichFindIterator<TSys, F, P, K>
+{
     type Item = PathBuf;
 
     fn next(&mut self) -> Option<Self::Item> {
-        for path in &mut self.paths {
-            if is_valid(&self.sys, &path, &mut self.nonfatal_error_handler) {
-                return Some(correct_casing(
-                    &self.sys,
-                    path,
-                    &mut self.nonfatal_error_handler,
-                ));
+        loop {
+            match self.next_checked()? {
+                Ok(path) => return Some(path),
+                Err(path) => self
+                    .nonfatal_error_handler
+                    .handle(NonFatalError::FoundRelativeToCwd(path)),
             }
         }
-        None
     }
 }
 
+/// A path that may be the binary being searched for.
+pub(crate) struct Candidate {
+    /// Index of the [`SearchDir`] this candidate is in.
+    dir: usize,
+    pub path: PathBuf,
+    /// Index of the path extension appended to the query, if any.
+    extension: Option<usize>,
+}
+
 struct PathsIter<P>
 where
     P: Iterator<Item = PathBuf>,
 {
-    paths: P,
-    current_path_with_index: Option<(PathBuf, usize)>,
+    paths: iter::Enumerate<P>,
+    current_path_with_index: Option<(usize, PathBuf, usize)>,
     path_extensions: Cow<'static, [String]>,
 }
 
@@ -216,15 +770,19 @@ impl<P> Iterator for PathsIter<P>
 where
     P: Iterator<Item = PathBuf>,
 {
-    type Item = PathBuf;
+    type Item = Candidate;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.path_extensions.is_empty() {
-            self.paths.next()
-        } else if let Some((p, index)) = self.current_path_with_index.take() {
+            self.paths.next().map(|(dir, path)| Candidate {
+                dir,
+                path,
+                extension: None,
+            })
+        } else if let Some((dir, p, index)) = self.current_path_with_index.take() {
             let next_index = index + 1;
             if next_index < self.path_extensions.len() {
-                self.current_path_with_index = Some((p.clone(), next_index));
+                self.current_path_with_index = Some((dir, p.clone(), next_index));
             }
             // Append the extension.
             let mut p = p.into_os_string();
@@ -232,9 +790,13 @@ where
             let ret = PathBuf::from(p);
             #[cfg(feature = "tracing")]
             tracing::trace!("possible extension: {}", ret.display());
-            Some(ret)
+            Some(Candidate {
+                dir,
+                path: ret,
+                extension: Some(index),
+            })
         } else {
-            let p = self.paths.next()?;
+            let (dir, p) = self.paths.next()?;
             if has_executable_extension(&p, &self.path_extensions) {
                 #[cfg(feature = "tracing")]
                 tracing::trace!(
@@ -254,30 +816,68 @@ where
                 // c:/windows/bin[.ext].EXE
                 // c:/windows/bin[.ext].CMD
                 // ...
-                self.current_path_with_index = Some((p.clone(), 0));
+                self.current_path_with_index = Some((dir, p.clone(), 0));
             }
-            Some(p)
+            Some(Candidate {
+                dir,
+                path: p,
+                extension: None,
+            })
         }
     }
 }
 
-fn tilde_expansion<TSys: Sys>(sys: TSys, p: &Path) -> Cow<'_, Path> {
-    let mut component_iter = p.components();
-    if let Some(Component::Normal(o)) = component_iter.next() {
-        if o == "~" {
-            let new_path = sys.home_dir();
-            if let Some(mut new_path) = new_path {
-                new_path.extend(component_iter);
-                #[cfg(feature = "tracing")]
-                tracing::trace!(
-                    "found tilde, substituting in user's home directory to get {}",
-                    new_path.display()
-                );
-                return Cow::Owned(new_path);
-            } else {
-                #[cfg(feature = "tracing")]
-                tracing::trace!("found tilde in path, but user's home directory couldn't be found");
-            }
+/// Returns the extensions to try on Windows, from `PATHEXT` and any override for the query.
+pub(crate) fn path_extensions<TSys: Sys>(
+    sys: TSys,
+    path_extensions: Option<&PathExtensions>,
+) -> Cow<'static, [String]> {
+    if !sys.is_windows() {
+        return Cow::Borrowed(&[]);
+    }
+    let with_dot = |extensions: &[String]| {
+        extensions
+            .iter()
+            .filter(|e| !e.is_empty())
+            .map(|e| {
+                if e.starts_with('.') {
+                    e.clone()
+                } else {
+                    format!(".{e}")
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    match path_extensions {
+        None => sys.env_windows_path_ext(),
+        Some(PathExtensions::Clear) => Cow::Borrowed(&[]),
+        Some(PathExtensions::Replace(extensions)) => Cow::Owned(with_dot(extensions)),
+        Some(PathExtensions::Append(extensions)) => {
+            let mut all = sys.env_windows_path_ext().into_owned();
+            all.extend(with_dot(extensions));
+            Cow::Owned(all)
+        }
+    }
+}
+
+pub(crate) fn has_tilde(p: &Path) -> bool {
+    matches!(p.components().next(), Some(Component::Normal(o)) if o == "~")
+}
+
+pub(crate) fn tilde_expansion<TSys: Sys>(sys: TSys, p: &Path) -> Cow<'_, Path> {
+    if has_tilde(p) {
+        let new_path = sys.home_dir();
+        if let Some(mut new_path) = new_path {
+            new_path.extend(p.components().skip(1));
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                "found tilde, substituting in user's home directory to get {}",
+                new_path.display()
+            );
+            return Cow::Owned(new_path);
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::trace!("found tilde in path, but user's home directory couldn't be found");
         }
     }
     Cow::Borrowed(p)
@@ -311,60 +911,172 @@ fn correct_casing<TSys: Sys, F: NonFatalErrorHandler>(
     p
 }
 
-#[cfg(feature = "regex")]
-struct WhichFindRegexIter<TSys: Sys, B: Borrow<Regex>, F: NonFatalErrorHandler> {
-    sys: TSys,
-    re: B,
-    paths: vec::IntoIter<PathBuf>,
-    nonfatal_error_handler: F,
-    current_read_dir_iter: Option<Box<dyn Iterator<Item = io::Result<TSys::ReadDirEntry>>>>,
+/// Decides which names, or full paths, a [`Finder::find_matching`] search returns.
+pub(crate) trait NameMatcher {
+    fn is_match(&self, haystack: &OsStr) -> bool;
 }
 
 #[cfg(feature = "regex")]
-impl<TSys: Sys, B: Borrow<Regex>, F: NonFatalErrorHandler> WhichFindRegexIter<TSys, B, F> {
-    pub fn new<T: AsRef<OsStr>>(
-        sys: TSys,
-        paths: Option<T>,
-        re: B,
-        nonfatal_error_handler: F,
-    ) -> Result<Self> {
-        let p = paths.ok_or(Error::CannotGetCurrentDirAndPathListEmpty)?;
-        let paths = sys.env_split_paths(p.as_ref());
-        Ok(WhichFindRegexIter {
-            sys,
-            re,
-            paths: paths.into_iter(),
-            nonfatal_error_handler,
-            current_read_dir_iter: None,
-        })
+impl<R: RegexLike> NameMatcher for R {
+    fn is_match(&self, haystack: &OsStr) -> bool {
+        self.is_match_os_str(haystack)
     }
 }
 
-#[cfg(feature = "regex")]
-impl<TSys: Sys, B: Borrow<Regex>, F: NonFatalErrorHandler> Iterator
-    for WhichFindRegexIter<TSys, B, F>
+/// A shell wildcard pattern, see [`wildcard_match`].
+pub(crate) struct Glob {
+    pattern: String,
+    case_insensitive: bool,
+}
+
+impl Glob {
+    /// Creates a glob that ignores case when `sys` emulates Windows, like its file names do.
+    pub fn new<TSys: Sys>(sys: TSys, pattern: &str) -> Self {
+        let case_insensitive = sys.is_windows();
+        Glob {
+            pattern: if case_insensitive {
+                pattern.to_lowercase()
+            } else {
+                pattern.to_owned()
+            },
+            case_insensitive,
+        }
+    }
+}
+
+impl NameMatcher for Glob {
+    fn is_match(&self, haystack: &OsStr) -> bool {
+        let haystack = haystack.to_string_lossy();
+        if self.case_insensitive {
+            wildcard_match(&self.pattern, &haystack.to_lowercase())
+        } else {
+            wildcard_match(&self.pattern, &haystack)
+        }
+    }
+}
+
+/// How [`Finder::find_matching`] matches. Regex searches can change these with
+/// [`WhichConfig::regex_checks`](crate::WhichConfig::regex_checks) and
+/// [`WhichConfig::regex_full_path`](crate::WhichConfig::regex_full_path).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MatchOptions {
+    /// Check matches like any other result.
+    pub checked: bool,
+    /// Match the full path of each candidate instead of its name.
+    pub full_path: bool,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            checked: true,
+            full_path: false,
+        }
+    }
+}
+
+/// The results of [`Finder::find_matching`], read one directory at a time.
+pub(crate) struct WhichFindMatchIter<
+    TSys: Sys,
+    M: NameMatcher,
+    F: NonFatalErrorHandler,
+    P: CandidateFilter,
+    K: Checker,
+> {
+    sys: TSys,
+    matcher: M,
+    /// The pattern, for error messages.
+    query: Option<OsString>,
+    options: MatchOptions,
+    path_extensions: Cow<'static, [String]>,
+    /// The cwd, if it's searched, followed by the directories of the path list.
+    dirs: Vec<SearchDir>,
+    next_dir: usize,
+    refuse_relative_path_entries: bool,
+    filter: P,
+    checker: K,
+    nonfatal_error_handler: F,
+    current_read_dir_iter: Option<Box<dyn Iterator<Item = io::Result<TSys::ReadDirEntry>>>>,
+    /// The directory being read is an empty or relative path list entry that's refused.
+    current_dir_is_relative: bool,
+}
+
+impl<TSys: Sys, M: NameMatcher, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>
+    WhichFindMatchIter<TSys, M, F, P, K>
 {
-    type Item = PathBuf;
+    /// Returns the first match, or an error describing the search if there isn't one.
+    pub(crate) fn first(&mut self) -> Result<PathBuf> {
+        match self.next_checked() {
+            Some(Ok(path)) => Ok(path),
+            Some(Err(path)) => Err(Error::FoundRelativeToCwd(path)),
+            None => Err(Error::CannotFindBinaryPath(SearchContext {
+                query: self.query.clone(),
+                paths: self.dirs.iter().map(|d| d.dir.clone()).collect(),
+                cwd: self
+                    .dirs
+                    .iter()
+                    .find(|d| d.index.is_none())
+                    .map(|d| d.dir.clone()),
+            })),
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Returns the next match, or `Err` with the match if it was found through an empty or
+    /// relative path list entry and those are being refused.
+    fn next_checked(&mut self) -> Option<std::result::Result<PathBuf, PathBuf>> {
         loop {
             if let Some(iter) = &mut self.current_read_dir_iter {
                 match iter.next() {
-                    Some(Ok(path)) => {
-                        if let Some(unicode_file_name) = path.file_name().to_str() {
-                            if self.re.borrow().is_match(unicode_file_name) {
-                                return Some(path.path());
-                            } else {
-                                #[cfg(feature = "tracing")]
-                                tracing::debug!("regex filtered out {}", unicode_file_name);
+                    Some(Ok(entry)) => {
+                        let path = entry.path();
+                        let file_name = entry.file_name();
+                        // Checked matches can also be compared by the name they can be run by,
+                        // without their `PATHEXT` extension on Windows.
+                        let name = if self.options.checked {
+                            match command_name(&self.sys, &file_name, &self.path_extensions) {
+                                Some((name, _)) => Some(name),
+                                None => continue,
                             }
                         } else {
+                            None
+                        };
+                        let is_match = if self.options.full_path {
+                            self.matcher.is_match(path.as_os_str())
+                        } else {
+                            self.matcher.is_match(&file_name)
+                                || name.is_some_and(|name| {
+                                    name != file_name && self.matcher.is_match(&name)
+                                })
+                        };
+                        if !is_match {
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!("pattern filtered out {}", path.display());
+                            continue;
+                        }
+                        if self.options.checked
+                            && !is_valid(
+                                &self.checker,
+                                &self.sys,
+                                &path,
+                                &mut self.nonfatal_error_handler,
+                            )
+                        {
+                            continue;
+                        }
+                        if !self.filter.accept(&path) {
+                            self.nonfatal_error_handler
+                                .handle(NonFatalError::RejectedByFilter(path));
+                            continue;
+                        }
+                        if self.current_dir_is_relative {
                             #[cfg(feature = "tracing")]
                             tracing::debug!(
-                                "regex unable to evaluate filename as it's not valid unicode. Lossy filename conversion: {}",
-                                path.file_name().to_string_lossy()
+                                "refusing {} as it was found relative to the current directory",
+                                path.display()
                             );
+                            return Some(Err(path));
                         }
+                        return Some(Ok(path));
                     }
                     Some(Err(e)) => {
                         self.nonfatal_error_handler.handle(NonFatalError::Io(e));
@@ -374,11 +1086,15 @@ impl<TSys: Sys, B: Borrow<Regex>, F: NonFatalErrorHandler> Iterator
                     }
                 }
             } else {
-                let path = self.paths.next();
-                if let Some(path) = path {
-                    match self.sys.read_dir(&path) {
+                let dir = self.dirs.get(self.next_dir);
+                if let Some(dir) = dir {
+                    self.next_dir += 1;
+                    match self.sys.read_dir(&dir.dir) {
                         Ok(new_read_dir_iter) => {
                             self.current_read_dir_iter = Some(new_read_dir_iter);
+                            self.current_dir_is_relative = self.refuse_relative_path_entries
+                                && dir.index.is_some()
+                                && dir.dir.is_relative();
                         }
                         Err(e) => {
                             self.nonfatal_error_handler.handle(NonFatalError::Io(e));
@@ -391,3 +1107,20 @@ impl<TSys: Sys, B: Borrow<Regex>, F: NonFatalErrorHandler> Iterator
         }
     }
 }
+
+impl<TSys: Sys, M: NameMatcher, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker> Iterator
+    for WhichFindMatchIter<TSys, M, F, P, K>
+{
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_checked()? {
+                Ok(path) => return Some(path),
+                Err(path) => self
+                    .nonfatal_error_handler
+                    .handle(NonFatalError::FoundRelativeToCwd(path)),
+            }
+        }
+    }
+}
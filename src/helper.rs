@@ -11,6 +11,98 @@ pub fn has_executable_extension<T: AsRef<Path>, S: AsRef<str>>(path: T, pathext:
     }
 }
 
+/// Optimal string alignment distance between `a` and `b`: the number of insertions, deletions,
+/// substitutions and transpositions of adjacent characters needed to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows of the distance matrix: two rows back, the previous one and the current one.
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Matches `name` against a shell wildcard pattern: `*` matches any run of characters, `?` any
+/// single character, and `[...]` any character in the set, which may contain ranges like `a-z`
+/// and be negated with a leading `!` or `^`. Other characters match themselves.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`: the pattern position after it, and the next name
+    // position it could swallow.
+    let mut backtrack = None;
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match_class(&pattern[p..], name[n]),
+            Some(&c) => (c == name[n]).then_some(1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(len), _) => {
+                p += len;
+                n += 1;
+            }
+            (None, Some((star_p, star_n))) => {
+                backtrack = Some((star_p, star_n + 1));
+                p = star_p;
+                n = star_n + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the `[...]` set at the start of `pattern`, returning the length of the
+/// set if it matches. A `[` without a closing `]` matches itself.
+fn match_class(pattern: &[char], c: char) -> Option<usize> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut found = false;
+    let mut first = true;
+    loop {
+        match pattern.get(i) {
+            None => return (c == '[').then_some(1),
+            Some(']') if !first => break,
+            Some(&start) => {
+                if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&e| e != ']')
+                {
+                    found |= (start..=pattern[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    found |= start == c;
+                    i += 1;
+                }
+            }
+        }
+        first = false;
+    }
+    (found != negated).then_some(i + 1)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -45,4 +137,32 @@ mod test {
             &["", "."]
         ));
     }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("pyhton", "python"), 1);
+        assert_eq!(edit_distance("carg", "cargo"), 1);
+        assert_eq!(edit_distance("", "ls"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        // Optimal string alignment doesn't edit a substring twice.
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.conf", "libc.conf"));
+        assert!(wildcard_match("*.conf", ".conf"));
+        assert!(!wildcard_match("*.conf", "libc.conf.bak"));
+        assert!(wildcard_match("lib?.so", "libc.so"));
+        assert!(!wildcard_match("lib?.so", "lib.so"));
+        assert!(wildcard_match("cargo-*-*", "cargo-clippy-driver"));
+        assert!(wildcard_match("[0-9][!a-z]*", "1B"));
+        assert!(!wildcard_match("[0-9][!a-z]*", "1b"));
+        assert!(wildcard_match("[]x]", "]"));
+        assert!(wildcard_match("a[b", "a[b"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("?", ""));
+    }
 }
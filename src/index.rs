@@ -0,0 +1,548 @@
+use crate::checker::is_valid;
+use crate::finder::{path_extensions, Finder, PathListEdits};
+use crate::resolver::Locked;
+use crate::scan::{command_name, lookup_keys, name_key, path_dirs};
+use crate::sys::{Sys, SysMetadata, SysReadDirEntry};
+use crate::{
+    CandidateFilter, Checker, CwdOption, Error, NonFatalError, NonFatalErrorHandler,
+    PathExtensions, PathListOption, Result, SearchContext,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, UNIX_EPOCH};
+
+const HEADER: &[u8] = b"which command index 2";
+
+/// An index of the commands in every directory of the path list, saved between runs so short
+/// lived programs don't have to search the path list each time.
+///
+/// The index maps each directory to the names of the entries in it, along with the directory's
+/// modification time and, where the platform has them, its inode number. A lookup only reads the
+/// metadata of the directories it goes through, and of the entries named like the command, which
+/// are checked with the [`Checker`] as usual. If any of the directories changed since it was
+/// indexed, or isn't indexed yet, the lookup falls back to a normal search.
+/// [`update`](CommandIndex::update) scans the directories that changed again and saves the
+/// index.
+///
+/// The index is kept in `$XDG_CACHE_HOME/which/command-index`, falling back to
+/// `~/.cache/which/command-index`, or `%LOCALAPPDATA%\which\command-index` on Windows, unless
+/// it was built with [`WhichConfig::command_index_at`](crate::WhichConfig::command_index_at).
+/// It's shared by every path list and checker, so directories are only scanned once however
+/// they're combined. Lookups and scans run on the calling thread, as
+/// [`WhichConfig::parallel`](crate::WhichConfig::parallel) doesn't apply to the index.
+///
+/// Like a shell's command hash table, it doesn't notice changes that leave the directory
+/// alone, such as making a file executable, and relies on the file system updating a
+/// directory's modification time whenever an entry is added, removed or renamed. Empty and
+/// relative path list entries are never indexed: lookups fall back to a normal search when the
+/// path list has any, and [`commands`](CommandIndex::commands) leaves them out.
+///
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "real-sys")]
+/// # {
+/// use which::WhichConfig;
+///
+/// let index = WhichConfig::new().command_index();
+/// let cargo = index.resolve("cargo");
+/// // Scan the directories that changed and save the index for the next run.
+/// index.update().unwrap();
+/// # }
+/// ```
+pub struct CommandIndex<TSys: Sys, F, C, K> {
+    sys: TSys,
+    path_list: PathListOption,
+    cwd: CwdOption,
+    path_list_edits: PathListEdits,
+    path_extensions: Option<PathExtensions>,
+    refuse_relative_path_entries: bool,
+    location: Option<PathBuf>,
+    index: Mutex<Index>,
+    nonfatal_error_handler: Mutex<F>,
+    filter: Mutex<C>,
+    checker: K,
+}
+
+#[derive(Default)]
+struct Index {
+    dirs: HashMap<PathBuf, IndexedDir>,
+    /// Whether `dirs` differs from the saved index.
+    dirty: bool,
+}
+
+struct IndexedDir {
+    stamp: Stamp,
+    /// The names of every entry in the directory.
+    files: Vec<OsString>,
+}
+
+/// What a directory looked like when it was indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stamp {
+    Missing,
+    Present {
+        /// The modification time, since the Unix epoch.
+        modified: Duration,
+        inode: Option<u64>,
+    },
+}
+
+impl<TSys: Sys, F: NonFatalErrorHandler, C: CandidateFilter, K: Checker>
+    CommandIndex<TSys, F, C, K>
+{
+    pub(crate) fn new(
+        finder: Finder<TSys, C, K>,
+        path_list: PathListOption,
+        cwd: CwdOption,
+        mut nonfatal_error_handler: F,
+        location: Option<PathBuf>,
+    ) -> Self {
+        let location = location.or_else(|| default_location(&finder.sys));
+        let index = match &location {
+            Some(location) => load(&finder.sys, location, &mut nonfatal_error_handler),
+            None => Index::default(),
+        };
+        CommandIndex {
+            path_list,
+            cwd,
+            path_list_edits: finder.path_list_edits,
+            path_extensions: finder.path_extensions,
+            refuse_relative_path_entries: finder.refuse_relative_path_entries,
+            location,
+            index: Mutex::new(index),
+            nonfatal_error_handler: Mutex::new(nonfatal_error_handler),
+            filter: Mutex::new(finder.filter),
+            checker: finder.checker,
+            sys: finder.sys,
+        }
+    }
+
+    /// Where the index is saved, or `None` if no cache directory could be found.
+    pub fn location(&self) -> Option<&Path> {
+        self.location.as_deref()
+    }
+
+    /// Returns the first result for `binary_name`, like
+    /// [`WhichConfig::first_result`](crate::WhichConfig::first_result), from the index if the
+    /// directories it goes through are unchanged.
+    pub fn resolve<T: AsRef<OsStr>>(&self, binary_name: T) -> Result<PathBuf> {
+        let binary_name = binary_name.as_ref();
+        if let Some(result) = self.lookup(binary_name) {
+            return result;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            "the command index can't answer for {}, searching",
+            binary_name.to_string_lossy()
+        );
+        let paths = self.path_list.clone().resolve(&self.sys);
+        let cwd = self.cwd.clone().resolve(&self.sys);
+        Finder::new(&self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions.clone())
+            .path_list_edits(self.path_list_edits.clone())
+            .filter(Locked(&self.filter))
+            .checker(&self.checker)
+            .find_first(
+                binary_name,
+                paths,
+                cwd,
+                Locked(&self.nonfatal_error_handler),
+            )
+    }
+
+    /// Lists every command on the path list, like `compgen -c`, with the paths it resolves to in
+    /// search order. Directories that changed are scanned again first; call
+    /// [`update`](CommandIndex::update) to save the result.
+    pub fn commands(&self) -> Result<BTreeMap<OsString, Vec<PathBuf>>> {
+        let dirs = path_dirs(
+            &self.sys,
+            self.path_list.clone().resolve(&self.sys),
+            &self.path_list_edits,
+        )?;
+        let mut index = self.lock_index();
+        self.refresh(&mut index, &dirs);
+
+        let is_windows = self.sys.is_windows();
+        let path_extensions = path_extensions(&self.sys, self.path_extensions.as_ref());
+        let mut found = Vec::new();
+        for (dir_index, dir) in dirs.iter().enumerate() {
+            let Some(indexed) = index.dirs.get(dir) else {
+                continue;
+            };
+            for file in &indexed.files {
+                if let Some((name, rank)) = command_name(&self.sys, file, &path_extensions) {
+                    found.push((dir_index, rank, name, dir.join(file)));
+                }
+            }
+        }
+        drop(index);
+        found.sort_by_key(|(dir_index, rank, _, _)| (*dir_index, *rank));
+
+        // On Windows the first spelling of a name is used for every command with that name.
+        let mut names = HashMap::new();
+        let mut commands = BTreeMap::<OsString, Vec<PathBuf>>::new();
+        for (_, _, name, path) in found {
+            // Entries that aren't commands are expected here, so rejections aren't reported.
+            if !self.checker.check(&self.sys, &path).is_match() || !self.accept(&path) {
+                continue;
+            }
+            let name = names
+                .entry(name_key(is_windows, &name))
+                .or_insert(name)
+                .clone();
+            commands.entry(name).or_default().push(path);
+        }
+        Ok(commands)
+    }
+
+    /// Scans the directories of the path list that changed since they were indexed, and saves
+    /// the index if anything changed.
+    pub fn update(&self) -> Result<()> {
+        let dirs = path_dirs(
+            &self.sys,
+            self.path_list.clone().resolve(&self.sys),
+            &self.path_list_edits,
+        )
+        .unwrap_or_default();
+        let mut index = self.lock_index();
+        self.refresh(&mut index, &dirs);
+        if !index.dirty {
+            return Ok(());
+        }
+        let Some(location) = &self.location else {
+            return Err(Error::CannotWriteIndex {
+                path: None,
+                source: io::Error::new(io::ErrorKind::NotFound, "no cache directory found"),
+            });
+        };
+        self.sys
+            .replace_file(location, &serialize(&index))
+            .map_err(|source| Error::CannotWriteIndex {
+                path: Some(location.clone()),
+                source,
+            })?;
+        index.dirty = false;
+        Ok(())
+    }
+
+    /// Answers from the index, or returns `None` if the index can't tell.
+    fn lookup(&self, binary_name: &OsStr) -> Option<Result<PathBuf>> {
+        if Path::new(binary_name).components().count() != 1 {
+            return None;
+        }
+        let dirs = path_dirs(
+            &self.sys,
+            self.path_list.clone().resolve(&self.sys),
+            &self.path_list_edits,
+        )
+        .ok()?;
+        if dirs.iter().any(|dir| dir.is_relative()) {
+            return None;
+        }
+
+        let path_extensions = path_extensions(&self.sys, self.path_extensions.as_ref());
+        let wanted = name_key(self.sys.is_windows(), binary_name);
+        let index = self.lock_index();
+        for dir in &dirs {
+            let indexed = index.dirs.get(dir)?;
+            if stamp(&self.sys, dir) != Some(indexed.stamp) {
+                return None;
+            }
+            let mut candidates: Vec<_> = indexed
+                .files
+                .iter()
+                .filter_map(|file| {
+                    lookup_keys(&self.sys, file, &path_extensions)
+                        .into_iter()
+                        .find(|(key, _)| *key == wanted)
+                        .map(|(_, rank)| (rank, file))
+                })
+                .collect();
+            candidates.sort_by_key(|(rank, _)| *rank);
+            for (_, file) in candidates {
+                let path = dir.join(file);
+                if is_valid(
+                    &self.checker,
+                    &self.sys,
+                    &path,
+                    &mut Locked(&self.nonfatal_error_handler),
+                ) && self.accept(&path)
+                {
+                    return Some(Ok(path));
+                }
+            }
+        }
+        Some(Err(Error::CannotFindBinaryPath(SearchContext {
+            query: Some(binary_name.to_os_string()),
+            paths: dirs,
+            cwd: None,
+        })))
+    }
+
+    /// Scans the absolute directories in `dirs` that changed since they were indexed.
+    fn refresh(&self, index: &mut Index, dirs: &[PathBuf]) {
+        for dir in dirs.iter().filter(|dir| dir.is_absolute()) {
+            // Read the stamp before scanning, so a change made during the scan makes the entry
+            // stale instead of being missed.
+            let Some(stamp) = stamp(&self.sys, dir) else {
+                index.dirty |= index.dirs.remove(dir).is_some();
+                continue;
+            };
+            if index.dirs.get(dir).is_some_and(|d| d.stamp == stamp) {
+                continue;
+            }
+            #[cfg(feature = "tracing")]
+            tracing::trace!("indexing {}", dir.display());
+            let files = match stamp {
+                Stamp::Missing => Some(Vec::new()),
+                Stamp::Present { .. } => self.scan(dir),
+            };
+            match files {
+                Some(files) => {
+                    index.dirs.insert(dir.clone(), IndexedDir { stamp, files });
+                    index.dirty = true;
+                }
+                None => index.dirty |= index.dirs.remove(dir).is_some(),
+            }
+        }
+    }
+
+    /// Lists the entries in `dir`, or returns `None` if it can't be read.
+    fn scan(&self, dir: &Path) -> Option<Vec<OsString>> {
+        let entries = match self.sys.read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.handle(NonFatalError::Io(e));
+                return None;
+            }
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => files.push(entry.file_name()),
+                Err(e) => self.handle(NonFatalError::Io(e)),
+            }
+        }
+        Some(files)
+    }
+
+    fn accept(&self, path: &Path) -> bool {
+        let accepted = self
+            .filter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .accept(path);
+        if !accepted {
+            self.handle(NonFatalError::RejectedByFilter(path.to_path_buf()));
+        }
+        accepted
+    }
+
+    fn handle(&self, e: NonFatalError) {
+        self.nonfatal_error_handler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle(e)
+    }
+
+    fn lock_index(&self) -> MutexGuard<'_, Index> {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn default_location<TSys: Sys>(sys: TSys) -> Option<PathBuf> {
+    let var = |name: &str| {
+        sys.env_var_os(OsStr::new(name))
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+    };
+    let cache = match var("XDG_CACHE_HOME") {
+        Some(cache) => cache,
+        None if sys.is_windows() => var("LOCALAPPDATA")?,
+        None => sys.home_dir()?.join(".cache"),
+    };
+    Some(cache.join("which").join("command-index"))
+}
+
+/// Returns what `dir` looks like now, or `None` if it can't be told whether it changed.
+fn stamp<TSys: Sys>(sys: TSys, dir: &Path) -> Option<Stamp> {
+    match sys.metadata(dir) {
+        Ok(metadata) => Some(Stamp::Present {
+            modified: metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?,
+            inode: metadata.inode(),
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Stamp::Missing),
+        Err(_) => None,
+    }
+}
+
+/// Reads the index saved at `location`. A missing or unreadable index is treated as empty.
+fn load<TSys: Sys, F: NonFatalErrorHandler>(
+    sys: TSys,
+    location: &Path,
+    nonfatal_error_handler: &mut F,
+) -> Index {
+    match sys.read_file(location) {
+        Ok(contents) => parse(&contents).unwrap_or_else(|| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("ignoring malformed command index {}", location.display());
+            Index {
+                dirty: true,
+                ..Default::default()
+            }
+        }),
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                nonfatal_error_handler.handle(NonFatalError::Io(e));
+            }
+            Index::default()
+        }
+    }
+}
+
+// The index is a line based text file. After the header, each directory is a `d` line, followed
+// by an `f` line for each of its entries:
+//
+//   d <tab> /usr/bin <tab> <seconds> <tab> <nanoseconds> <tab> <inode or ->
+//   f <tab> ls
+//   d <tab> /missing <tab> -
+//
+// Backslashes, tabs and newlines in paths and names are escaped.
+
+fn serialize(index: &Index) -> Vec<u8> {
+    let mut out = HEADER.to_vec();
+    out.push(b'\n');
+    let mut dirs: Vec<_> = index.dirs.iter().collect();
+    dirs.sort_by_key(|(dir, _)| *dir);
+    for (dir, indexed) in dirs {
+        let Some(dir) = encode(dir.as_os_str()) else {
+            continue;
+        };
+        out.extend_from_slice(b"d\t");
+        out.extend_from_slice(&dir);
+        match indexed.stamp {
+            Stamp::Missing => out.extend_from_slice(b"\t-\n"),
+            Stamp::Present { modified, inode } => {
+                let inode = inode.map_or_else(|| "-".to_owned(), |inode| inode.to_string());
+                out.extend_from_slice(
+                    format!(
+                        "\t{}\t{}\t{inode}\n",
+                        modified.as_secs(),
+                        modified.subsec_nanos()
+                    )
+                    .as_bytes(),
+                );
+            }
+        }
+        for file in indexed.files.iter().filter_map(|f| encode(f)) {
+            out.extend_from_slice(b"f\t");
+            out.extend_from_slice(&file);
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+fn parse(contents: &[u8]) -> Option<Index> {
+    let mut lines = contents.split(|&b| b == b'\n');
+    if lines.next()? != HEADER {
+        return None;
+    }
+    let mut dirs = HashMap::new();
+    let mut current: Option<(PathBuf, IndexedDir)> = None;
+    for line in lines.filter(|line| !line.is_empty()) {
+        let mut fields = line.split(|&b| b == b'\t');
+        match fields.next()? {
+            b"d" => {
+                let dir = PathBuf::from(decode(fields.next()?)?);
+                let stamp = match fields.next()? {
+                    b"-" => Stamp::Missing,
+                    secs => {
+                        let nanos = fields.next()?;
+                        let inode = match fields.next()? {
+                            b"-" => None,
+                            inode => Some(number(inode)?),
+                        };
+                        Stamp::Present {
+                            modified: Duration::new(number(secs)?, number(nanos)?),
+                            inode,
+                        }
+                    }
+                };
+                let files = Vec::new();
+                if let Some((dir, indexed)) = current.replace((dir, IndexedDir { stamp, files })) {
+                    dirs.insert(dir, indexed);
+                }
+            }
+            b"f" => current.as_mut()?.1.files.push(decode(fields.next()?)?),
+            _ => return None,
+        }
+        if fields.next().is_some() {
+            return None;
+        }
+    }
+    dirs.extend(current);
+    Some(Index { dirs, dirty: false })
+}
+
+fn number<T: std::str::FromStr>(field: &[u8]) -> Option<T> {
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+/// Escapes a path or name for the index, or returns `None` if it can't be stored, which is
+/// only the case for names that aren't valid Unicode outside of Unix.
+fn encode(name: &OsStr) -> Option<Vec<u8>> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        name.as_bytes()
+    };
+    #[cfg(not(unix))]
+    let bytes = name.to_str()?.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b => out.push(b),
+        }
+    }
+    Some(out)
+}
+
+fn decode(field: &[u8]) -> Option<OsString> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut escaped = false;
+    for &b in field {
+        if escaped {
+            bytes.push(match b {
+                b'\\' => b'\\',
+                b't' => b'\t',
+                b'n' => b'\n',
+                _ => return None,
+            });
+            escaped = false;
+        } else if b == b'\\' {
+            escaped = true;
+        } else {
+            bytes.push(b);
+        }
+    }
+    if escaped {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Some(OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8(bytes).ok().map(OsString::from)
+    }
+}
@@ -16,21 +16,50 @@
 //! # }
 //! ```
 
+mod cache;
 mod checker;
 mod error;
+mod explain;
 mod finder;
 mod helper;
+mod index;
+mod library;
+mod lint;
+mod many;
+mod parallel;
+mod query;
+#[cfg(feature = "regex")]
+mod regex_like;
+mod resolver;
+mod scan;
+mod shadow;
+mod suggest;
 pub mod sys;
 #[cfg(all(windows, feature = "real-sys"))]
 mod win_ffi;
 
+use std::collections::BTreeMap;
 use std::fmt;
+use std::num::NonZeroUsize;
 use std::path;
 
 use std::ffi::{OsStr, OsString};
 
+pub use crate::cache::CachingResolver;
+pub use crate::checker::{Checker, DefaultChecker, FileChecker};
 pub use crate::error::*;
-use crate::finder::Finder;
+pub use crate::explain::*;
+use crate::finder::{Finder, Glob, MatchOptions, NameMatcher, PathListEdits, WhichFindMatchIter};
+pub use crate::index::CommandIndex;
+pub use crate::library::LibraryConfig;
+pub use crate::lint::{PathDiagnostic, PathDiagnosticKind, Severity};
+pub use crate::parallel::{Parallel, SearchMode, Sequential};
+pub use crate::query::{Query, QueryScope};
+#[cfg(feature = "regex")]
+pub use crate::regex_like::RegexLike;
+pub use crate::resolver::Resolver;
+pub use crate::shadow::Shadowing;
+pub use crate::suggest::Suggestion;
 use crate::sys::Sys;
 
 /// Find an executable binary's path by name.
@@ -56,7 +85,9 @@ use crate::sys::Sys;
 /// ```
 #[cfg(feature = "real-sys")]
 pub fn which<T: AsRef<OsStr>>(binary_name: T) -> Result<path::PathBuf> {
-    which_all(binary_name).and_then(|mut i| i.next().ok_or(Error::CannotFindBinaryPath))
+    let cwd = sys::RealSys.current_dir().ok();
+
+    Finder::new(&sys::RealSys).find_first(binary_name, sys::RealSys.env_path(), cwd, Noop)
 }
 
 /// Find an executable binary's path by name, ignoring `cwd`.
@@ -81,7 +112,12 @@ pub fn which<T: AsRef<OsStr>>(binary_name: T) -> Result<path::PathBuf> {
 /// ```
 #[cfg(feature = "real-sys")]
 pub fn which_global<T: AsRef<OsStr>>(binary_name: T) -> Result<path::PathBuf> {
-    which_all_global(binary_name).and_then(|mut i| i.next().ok_or(Error::CannotFindBinaryPath))
+    Finder::new(&sys::RealSys).find_first(
+        binary_name,
+        sys::RealSys.env_path(),
+        Option::<&Path>::None,
+        Noop,
+    )
 }
 
 /// Find all binaries with `binary_name` using `cwd` to resolve relative paths.
@@ -107,6 +143,11 @@ pub fn which_all_global<T: AsRef<OsStr>>(
 
 /// Find all binaries matching a regular expression in a the system PATH.
 ///
+/// Matches are checked like the result of [`which`]: directories and files that aren't
+/// executable are skipped. On Windows the regex is matched against the name both with and
+/// without its `PATHEXT` extension, so `^python$` and `\.exe$` both find `python.exe`, and files
+/// with other extensions are skipped.
+///
 /// Only available when feature `regex` is enabled.
 ///
 /// # Arguments
@@ -138,12 +179,36 @@ pub fn which_all_global<T: AsRef<OsStr>>(
 ///     .for_each(|pth| println!("{}", pth.to_string_lossy()));
 /// ```
 #[cfg(all(feature = "regex", feature = "real-sys"))]
-pub fn which_re(
-    regex: impl std::borrow::Borrow<Regex>,
-) -> Result<impl Iterator<Item = path::PathBuf>> {
+pub fn which_re(regex: impl RegexLike) -> Result<impl Iterator<Item = path::PathBuf>> {
     which_re_in(regex, sys::RealSys.env_path())
 }
 
+/// Find the first result for each of `names` in the system PATH, reading each directory once.
+///
+/// Names that weren't found are left out of the map. See [`WhichConfig::resolve_many`] to get
+/// every result for each name, or to search somewhere else.
+///
+/// # Example
+///
+/// ```
+/// let tools = which::which_many(["cargo", "rustc", "rustup"]).unwrap();
+/// if let Some(cargo) = tools.get(std::ffi::OsStr::new("cargo")) {
+///     println!("cargo is {}", cargo.display());
+/// }
+/// ```
+#[cfg(feature = "real-sys")]
+pub fn which_many<I, T>(names: I) -> Result<BTreeMap<OsString, path::PathBuf>>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    Ok(WhichConfig::new()
+        .resolve_many(names)?
+        .into_iter()
+        .filter_map(|(name, paths)| Some((name, paths.into_iter().next()?)))
+        .collect())
+}
+
 /// Find `binary_name` in the path list `paths`, using `cwd` to resolve relative paths.
 #[cfg(feature = "real-sys")]
 pub fn which_in<T, U, V>(binary_name: T, paths: Option<U>, cwd: V) -> Result<path::PathBuf>
@@ -152,12 +217,13 @@ where
     U: AsRef<OsStr>,
     V: AsRef<path::Path>,
 {
-    which_in_all(binary_name, paths, cwd)
-        .and_then(|mut i| i.next().ok_or(Error::CannotFindBinaryPath))
+    Finder::new(&sys::RealSys).find_first(binary_name, paths, Some(cwd), Noop)
 }
 
 /// Find all binaries matching a regular expression in a list of paths.
 ///
+/// Matches are checked the same way as by [`which_re`].
+///
 /// Only available when feature `regex` is enabled.
 ///
 /// # Arguments
@@ -181,13 +247,61 @@ where
 /// ```
 #[cfg(all(feature = "regex", feature = "real-sys"))]
 pub fn which_re_in<T>(
-    regex: impl std::borrow::Borrow<Regex>,
+    regex: impl RegexLike,
     paths: Option<T>,
 ) -> Result<impl Iterator<Item = path::PathBuf>>
 where
     T: AsRef<OsStr>,
 {
-    Finder::new(&sys::RealSys).find_re(regex, paths, Noop)
+    Finder::new(&sys::RealSys).find_matching(
+        regex,
+        None,
+        paths,
+        Option::<&Path>::None,
+        Default::default(),
+        Noop,
+    )
+}
+
+/// Find all binaries matching a shell wildcard pattern, such as `cargo-*`, in the system PATH.
+///
+/// See [`WhichConfig::glob`] for the pattern syntax. Unlike `which_re` this doesn't need the
+/// `regex` feature.
+///
+/// # Examples
+///
+/// Find all cargo subcommand executables on the path:
+///
+/// ```
+/// use which::which_glob;
+///
+/// which_glob("cargo-*").unwrap()
+///     .for_each(|pth| println!("{}", pth.to_string_lossy()));
+/// ```
+#[cfg(feature = "real-sys")]
+pub fn which_glob(pattern: &str) -> Result<impl Iterator<Item = path::PathBuf>> {
+    which_glob_in(pattern, sys::RealSys.env_path())
+}
+
+/// Find all binaries matching a shell wildcard pattern in a list of paths.
+///
+/// Matches are checked the same way as by [`which_glob`].
+#[cfg(feature = "real-sys")]
+pub fn which_glob_in<T>(
+    pattern: &str,
+    paths: Option<T>,
+) -> Result<impl Iterator<Item = path::PathBuf>>
+where
+    T: AsRef<OsStr>,
+{
+    Finder::new(&sys::RealSys).find_matching(
+        Glob::new(sys::RealSys, pattern),
+        Some(pattern.into()),
+        paths,
+        Option::<&Path>::None,
+        Default::default(),
+        Noop,
+    )
 }
 
 /// Find all binaries with `binary_name` in the path list `paths`, using `cwd` to resolve relative paths.
@@ -218,17 +332,43 @@ where
     Finder::new(&sys::RealSys).find(binary_name, paths, Option::<&Path>::None, Noop)
 }
 
+/// Diagnose problems with the entries of the `PATH` environment variable itself, such as
+/// missing directories, duplicates and directories other users can write to.
+///
+/// # Example
+///
+/// ```no_run
+/// for diagnostic in which::lint_path().unwrap() {
+///     eprintln!("{:?}: {diagnostic}", diagnostic.severity());
+/// }
+/// ```
+#[cfg(feature = "real-sys")]
+pub fn lint_path() -> Result<Vec<PathDiagnostic>> {
+    WhichConfig::new().lint_path()
+}
+
 /// A wrapper containing all functionality in this crate.
-pub struct WhichConfig<TSys: sys::Sys, F = Noop> {
+pub struct WhichConfig<TSys: sys::Sys, F = Noop, C = Noop, K = DefaultChecker, M = Sequential> {
     cwd: CwdOption,
-    custom_path_list: Option<OsString>,
+    path_list: PathListOption,
+    refuse_relative_path_entries: bool,
+    path_extensions: Option<PathExtensions>,
+    path_list_edits: PathListEdits,
     binary_name: Option<OsString>,
     nonfatal_error_handler: F,
+    filter: C,
+    checker: K,
+    #[cfg(feature = "regex")]
+    /// The regex, and its pattern for error messages.
+    regex: Option<(Box<dyn RegexLike + Send + Sync>, String)>,
     #[cfg(feature = "regex")]
-    regex: Option<Regex>,
+    regex_options: MatchOptions,
+    glob: Option<String>,
+    mode: M,
     sys: TSys,
 }
 
+#[derive(Clone)]
 enum CwdOption {
     Unspecified,
     UseSysCwd,
@@ -236,6 +376,73 @@ enum CwdOption {
     UseCustomCwd(path::PathBuf),
 }
 
+impl CwdOption {
+    fn resolve(self, sys: impl Sys) -> Option<path::PathBuf> {
+        match self {
+            CwdOption::RefuseCwd => None,
+            CwdOption::UseCustomCwd(custom) => Some(custom),
+            CwdOption::UseSysCwd | CwdOption::Unspecified => sys.current_dir().ok(),
+        }
+    }
+
+    /// The directory a regex or glob search looks in before the path list. Unlike other
+    /// searches, this is only the current directory when it was asked for explicitly.
+    fn resolve_for_pattern(&self, sys: impl Sys) -> Option<path::PathBuf> {
+        match self {
+            CwdOption::Unspecified | CwdOption::RefuseCwd => None,
+            CwdOption::UseCustomCwd(custom) => Some(custom.clone()),
+            CwdOption::UseSysCwd => sys.current_dir().ok(),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum PathListOption {
+    System,
+    Custom(OsString),
+    Var(OsString),
+    /// Set by [`WhichConfig::query`]: the path list in a variable, or with `None` only the
+    /// directories added with [`WhichConfig::prepend_paths`] and [`WhichConfig::append_paths`].
+    Scoped(Option<OsString>),
+}
+
+impl PathListOption {
+    fn resolve(self, sys: impl Sys) -> Option<OsString> {
+        match self {
+            PathListOption::System => sys.env_path(),
+            PathListOption::Custom(custom) => Some(custom),
+            PathListOption::Var(name) | PathListOption::Scoped(Some(name)) => sys.env_var_os(&name),
+            PathListOption::Scoped(None) => None,
+        }
+    }
+}
+
+/// A directory to leave out of the search, see [`WhichConfig::exclude_path`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExcludePath {
+    /// Leave out entries equal to this path.
+    Exact(path::PathBuf),
+    /// Leave out entries equal to or below this path, e.g. `/mnt/c` leaves out `/mnt/c/Windows`
+    /// but not `/mnt/cdrom`.
+    Prefix(path::PathBuf),
+    /// Leave out entries that resolve to the same directory as this path once symlinks are
+    /// resolved with [`Sys::canonicalize`].
+    Canonical(path::PathBuf),
+}
+
+/// How [`WhichConfig::path_extensions`] changes the extensions tried on Windows.
+///
+/// Extensions are written like in `PATHEXT`, e.g. `.EXE`. A missing leading dot is added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathExtensions {
+    /// Try these extensions after those in `PATHEXT`.
+    Append(Vec<String>),
+    /// Try these extensions instead of those in `PATHEXT`.
+    Replace(Vec<String>),
+    /// Don't try any extensions, only the name as given.
+    Clear,
+}
+
 /// A handler for non-fatal errors which does nothing with them.
 #[derive(Default, Debug, Clone)]
 pub struct Noop;
@@ -264,16 +471,49 @@ where
     }
 }
 
+/// Decides whether a path that passed the usual checks is accepted as a match, see
+/// [`WhichConfig::filter`].
+///
+/// This trait is implemented for any closure or function that takes a `&Path` and returns a `bool`.
+/// [`Noop`] accepts everything.
+pub trait CandidateFilter {
+    fn accept(&mut self, candidate: &path::Path) -> bool;
+}
+
+impl CandidateFilter for Noop {
+    fn accept(&mut self, _: &path::Path) -> bool {
+        true
+    }
+}
+
+impl<T> CandidateFilter for T
+where
+    T: FnMut(&path::Path) -> bool,
+{
+    fn accept(&mut self, candidate: &path::Path) -> bool {
+        (self)(candidate)
+    }
+}
+
 #[cfg(feature = "real-sys")]
 impl<F: Default> Default for WhichConfig<&sys::RealSys, F> {
     fn default() -> Self {
         Self {
             cwd: CwdOption::Unspecified,
-            custom_path_list: None,
+            path_list: PathListOption::System,
+            refuse_relative_path_entries: false,
+            path_extensions: None,
+            path_list_edits: PathListEdits::default(),
             binary_name: None,
             nonfatal_error_handler: F::default(),
+            filter: Noop,
+            checker: DefaultChecker,
             #[cfg(feature = "regex")]
             regex: None,
+            #[cfg(feature = "regex")]
+            regex_options: Default::default(),
+            glob: None,
+            mode: Sequential,
             sys: &sys::RealSys,
         }
     }
@@ -300,27 +540,39 @@ impl<TSys: Sys> WhichConfig<TSys, Noop> {
     pub fn new_with_sys(sys: TSys) -> Self {
         Self {
             cwd: CwdOption::Unspecified,
-            custom_path_list: None,
+            path_list: PathListOption::System,
+            refuse_relative_path_entries: false,
+            path_extensions: None,
+            path_list_edits: PathListEdits::default(),
             binary_name: None,
             nonfatal_error_handler: Noop,
+            filter: Noop,
+            checker: DefaultChecker,
             #[cfg(feature = "regex")]
             regex: None,
+            #[cfg(feature = "regex")]
+            regex_options: Default::default(),
+            glob: None,
+            mode: Sequential,
             sys,
         }
     }
 }
 
-impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
+impl<
+        'a,
+        TSys: Sys + 'a,
+        F: NonFatalErrorHandler + 'a,
+        C: CandidateFilter + 'a,
+        K: Checker + 'a,
+        M,
+    > WhichConfig<TSys, F, C, K, M>
+{
     /// Whether or not to use the current working directory. `true` by default.
     ///
-    /// # Panics
-    ///
-    /// If regex was set previously, and you've just passed in `use_cwd: true`, this will panic.
+    /// A [`regex`](Self::regex) search only looks in the current working directory when this
+    /// is set to `true` explicitly, see [`Self::regex`].
     pub fn system_cwd(mut self, use_cwd: bool) -> Self {
-        #[cfg(feature = "regex")]
-        if self.regex.is_some() && use_cwd {
-            panic!("which can't use regex and cwd at the same time!")
-        }
         // Otherwise, keep custom cwd if specified.
         self.cwd = if use_cwd {
             CwdOption::UseSysCwd
@@ -332,14 +584,8 @@ impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
 
     /// Sets a custom path for resolving relative paths.
     ///
-    /// # Panics
-    ///
-    /// If regex was set previously, this will panic.
+    /// A [`regex`](Self::regex) search also looks in this directory, before the path list.
     pub fn custom_cwd(mut self, cwd: path::PathBuf) -> Self {
-        #[cfg(feature = "regex")]
-        if self.regex.is_some() {
-            panic!("which can't use regex and cwd at the same time!")
-        }
         self.cwd = CwdOption::UseCustomCwd(cwd);
         self
     }
@@ -349,11 +595,15 @@ impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
     /// When `Regex` is disabled this function takes the unit type as a stand in. The parameter will change when
     /// `Regex` is enabled.
     ///
+    /// If a cwd (aka current working directory) was set with [`Self::custom_cwd`] or
+    /// `system_cwd(true)`, that directory is searched first, followed by the path list. Without
+    /// either only the path list is searched.
+    ///
     /// # Panics
     ///
-    /// If the `regex` feature wasn't turned on for this crate this will always panic. Additionally if a
-    /// `cwd` (aka current working directory) or `binary_name` was set previously, this will panic, as those options
-    /// are incompatible with `regex`.
+    /// If the `regex` feature wasn't turned on for this crate this will always panic. Additionally if
+    /// `binary_name` or a `glob` was set previously, this will panic, as those are incompatible
+    /// with `regex`.
     #[allow(unused_variables)]
     #[allow(unused_mut)]
     pub fn regex(mut self, regex: Regex) -> Self {
@@ -363,19 +613,63 @@ impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
         }
         #[cfg(feature = "regex")]
         {
-            if matches!(self.cwd, CwdOption::UseSysCwd)
-                || matches!(self.cwd, CwdOption::UseCustomCwd(_))
-            {
-                panic!("which can't use regex and cwd at the same time!")
-            }
             if self.binary_name.is_some() {
                 panic!("which can't use `binary_name` and `regex` at the same time!");
             }
-            self.regex = Some(regex);
+            if self.glob.is_some() {
+                panic!("which can't use `regex` and `glob` at the same time!");
+            }
+            let pattern = regex.as_str().to_owned();
+            self.regex = Some((Box::new(regex), pattern));
             self
         }
     }
 
+    /// Sets a byte oriented path name regex to search for, instead of [`Self::regex`]. Unlike a
+    /// [`regex::Regex`], it can match file names that aren't valid Unicode, see [`RegexLike`].
+    ///
+    /// Only available when feature `regex` is enabled.
+    ///
+    /// # Panics
+    ///
+    /// If `binary_name` or a `glob` was set previously, this will panic, as those are
+    /// incompatible with `regex`.
+    #[cfg(feature = "regex")]
+    pub fn bytes_regex(mut self, regex: regex::bytes::Regex) -> Self {
+        if self.binary_name.is_some() {
+            panic!("which can't use `binary_name` and `regex` at the same time!");
+        }
+        if self.glob.is_some() {
+            panic!("which can't use `regex` and `glob` at the same time!");
+        }
+        let pattern = regex.as_str().to_owned();
+        self.regex = Some((Box::new(regex), pattern));
+        self
+    }
+
+    /// Sets whether matches of the [`regex`](Self::regex) are checked like any other result,
+    /// which is the default. Pass `false` to return every directory entry whose file name
+    /// matches, including directories and files that aren't executable, as
+    /// regex searches did before.
+    ///
+    /// Only available when feature `regex` is enabled.
+    #[cfg(feature = "regex")]
+    pub fn regex_checks(mut self, regex_checks: bool) -> Self {
+        self.regex_options.checked = regex_checks;
+        self
+    }
+
+    /// Sets whether the [`regex`](Self::regex) is matched against the full path of each
+    /// candidate, such as `/usr/local/bin/python3`, instead of only its name. `false` by
+    /// default.
+    ///
+    /// Only available when feature `regex` is enabled.
+    #[cfg(feature = "regex")]
+    pub fn regex_full_path(mut self, full_path: bool) -> Self {
+        self.regex_options.full_path = full_path;
+        self
+    }
+
     /// Sets the path name to search for. You ***MUST*** call this, or [`Self::regex`] prior to searching.
     ///
     /// # Panics
@@ -386,19 +680,287 @@ impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
         if self.regex.is_some() {
             panic!("which can't use `binary_name` and `regex` at the same time!");
         }
+        if self.glob.is_some() {
+            panic!("which can't use `binary_name` and `glob` at the same time!");
+        }
         self.binary_name = Some(name);
         self
     }
 
+    /// Sets a shell wildcard pattern to search for, such as `cargo-*`, `python3.?` or
+    /// `clang-1[0-9]`, instead of [`Self::binary_name`]. Unlike [`Self::regex`] this doesn't need
+    /// the `regex` feature.
+    ///
+    /// `*` matches any run of characters, `?` any single character, and `[...]` any character in
+    /// the set, which may contain ranges and be negated with a leading `!` or `^`. The pattern
+    /// must match the whole name. Matches are checked like any other result, and on Windows the
+    /// pattern is matched against the name both with and without its `PATHEXT` extension,
+    /// ignoring case, so `python*` and `*.exe` both find `python.exe`.
+    ///
+    /// A cwd is searched first, like for [`Self::regex`].
+    ///
+    /// # Panics
+    ///
+    /// If `binary_name` or a `regex` was set previously, this will panic, as those are
+    /// incompatible with `glob`.
+    pub fn glob(mut self, pattern: impl Into<String>) -> Self {
+        if self.binary_name.is_some() {
+            panic!("which can't use `binary_name` and `glob` at the same time!");
+        }
+        #[cfg(feature = "regex")]
+        if self.regex.is_some() {
+            panic!("which can't use `regex` and `glob` at the same time!");
+        }
+        self.glob = Some(pattern.into());
+        self
+    }
+
+    /// Sets what to search for, and where, from a `where.exe` style [`Query`] such as
+    /// `$JAVA_HOME:java*` or `/opt/tools:clang-1[0-9]`.
+    ///
+    /// A [`QueryScope::Dir`] replaces the path list with that directory, and a
+    /// [`QueryScope::Var`] with the path list in that environment variable. Directories added
+    /// with [`Self::prepend_paths`] and [`Self::append_paths`] are still searched. The pattern
+    /// is set with [`Self::glob`] if it contains wildcards, or [`Self::binary_name`] otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use which::WhichConfig;
+    ///
+    /// if let Ok(query) = "$JAVA_HOME:java*".parse() {
+    ///     let javas = WhichConfig::new().query(query).all_results();
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `binary_name`, a `regex` or a `glob` was set previously, this will panic. A query with
+    /// a directory or variable scope also panics if [`Self::custom_path_list`] or
+    /// [`Self::path_list_var`] was set previously, as the scope replaces the path list.
+    pub fn query(mut self, query: Query) -> Self {
+        let is_glob = query.is_glob();
+        let scope = match query.scope {
+            QueryScope::PathList => None,
+            QueryScope::Dir(dir) => {
+                self.path_list_edits.prepend.insert(0, dir);
+                Some(None)
+            }
+            QueryScope::Var(name) => Some(Some(name)),
+        };
+        if let Some(scope) = scope {
+            if let PathListOption::Custom(_) | PathListOption::Var(_) = self.path_list {
+                panic!("which can't use a scoped `query` and a custom path list at the same time!");
+            }
+            self.path_list = PathListOption::Scoped(scope);
+        }
+        if is_glob {
+            self.glob(query.pattern)
+        } else {
+            self.binary_name(query.pattern.into())
+        }
+    }
+
     /// Uses the given string instead of the `PATH` env variable.
+    ///
+    /// # Panics
+    ///
+    /// If a [`Self::query`] with a directory or variable scope was set previously, this will
+    /// panic.
     pub fn custom_path_list(mut self, custom_path_list: OsString) -> Self {
-        self.custom_path_list = Some(custom_path_list);
+        self.set_path_list(PathListOption::Custom(custom_path_list));
         self
     }
 
     /// Uses the `PATH` env variable. Enabled by default.
+    ///
+    /// # Panics
+    ///
+    /// If a [`Self::query`] with a directory or variable scope was set previously, this will
+    /// panic.
     pub fn system_path_list(mut self) -> Self {
-        self.custom_path_list = None;
+        self.set_path_list(PathListOption::System);
+        self
+    }
+
+    /// Uses the given env variable instead of `PATH`, e.g. `MANPATH` or `PYTHONPATH`. It's split
+    /// and has `~` expanded the same way as `PATH`.
+    ///
+    /// The variable is read with [`Sys::env_var_os`]. Combine this with [`Self::files`] to look
+    /// for files that aren't executable.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use which::WhichConfig;
+    ///
+    /// let page = WhichConfig::new()
+    ///     .path_list_var("MANPATH")
+    ///     .subpaths(["man1"])
+    ///     .files()
+    ///     .binary_name("ls.1".into())
+    ///     .first_result();
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If a [`Self::query`] with a directory or variable scope was set previously, this will
+    /// panic.
+    pub fn path_list_var(mut self, name: impl Into<OsString>) -> Self {
+        self.set_path_list(PathListOption::Var(name.into()));
+        self
+    }
+
+    fn set_path_list(&mut self, path_list: PathListOption) {
+        if let PathListOption::Scoped(_) = self.path_list {
+            panic!("which can't use a scoped `query` and a custom path list at the same time!");
+        }
+        self.path_list = path_list;
+    }
+
+    /// Searches the given relative directories below each directory of the path list, instead of
+    /// the directory itself, e.g. `share/man/man1` below each prefix in `CMAKE_PREFIX_PATH`.
+    ///
+    /// For each directory of the path list, the subpaths are searched in the order they were
+    /// given, before moving on to the next directory. Queries containing a path separator aren't
+    /// affected.
+    pub fn subpaths<I, P>(mut self, subpaths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<path::PathBuf>,
+    {
+        self.path_list_edits
+            .subpaths
+            .extend(subpaths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Searches the given directories before those in the path list.
+    ///
+    /// Directories from every call are searched in the order they were given. This works with
+    /// both [`Self::system_path_list`] and [`Self::custom_path_list`], and also when there's no
+    /// `PATH` at all.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use which::WhichConfig;
+    ///
+    /// let rustc = WhichConfig::new()
+    ///     .binary_name("rustc".into())
+    ///     .prepend_paths(["/opt/rust/bin"])
+    ///     .first_result();
+    /// # }
+    /// ```
+    pub fn prepend_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<path::PathBuf>,
+    {
+        self.path_list_edits
+            .prepend
+            .extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Searches the given directories after those in the path list, as fallbacks.
+    ///
+    /// Directories from every call are searched in the order they were given. This works with
+    /// both [`Self::system_path_list`] and [`Self::custom_path_list`], and also when there's no
+    /// `PATH` at all.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use which::WhichConfig;
+    ///
+    /// let cargo = WhichConfig::new()
+    ///     .binary_name("cargo".into())
+    ///     .append_paths(["~/.cargo/bin"])
+    ///     .first_result();
+    /// # }
+    /// ```
+    pub fn append_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<path::PathBuf>,
+    {
+        self.path_list_edits
+            .append
+            .extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leaves a directory out of the search. Can be called more than once.
+    ///
+    /// Applies to every directory searched, including those added by [`Self::prepend_paths`]
+    /// and [`Self::append_paths`], after `~` has been expanded in both the directory and the
+    /// excluded path. Paths are compared case insensitively on Windows, except for
+    /// [`ExcludePath::Canonical`], which is left to [`Sys::canonicalize`]. Queries containing a
+    /// path separator aren't affected.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use which::{ExcludePath, WhichConfig};
+    ///
+    /// // Don't pick up Windows programs under WSL.
+    /// let git = WhichConfig::new()
+    ///     .binary_name("git".into())
+    ///     .exclude_path(ExcludePath::Prefix("/mnt/c".into()))
+    ///     .first_result();
+    /// # }
+    /// ```
+    pub fn exclude_path(mut self, exclude: ExcludePath) -> Self {
+        self.path_list_edits.exclude.push(exclude);
+        self
+    }
+
+    /// Refuse matches found through an empty or relative entry in the path list. `false` by default.
+    ///
+    /// Such entries are resolved against the current working directory, which lets anyone who can
+    /// write to it plant binaries that take precedence over the intended ones. When enabled,
+    /// [`Self::first_result`] returns [`Error::FoundRelativeToCwd`] if the first match was found
+    /// this way, and [`Self::all_results`] skips these matches, passing
+    /// [`NonFatalError::FoundRelativeToCwd`] to the non-fatal error handler instead. The scans
+    /// done by [`Self::all_shadowed`], [`Self::suggestions`] and [`Self::resolve_many`] skip them
+    /// the same way, and [`Self::explain`] reports them as
+    /// [`CandidateOutcome::RelativePathEntry`]. This applies to [`Self::regex`] and
+    /// [`Self::glob`] searches too, though not to the cwd they search when it's set
+    /// explicitly.
+    ///
+    /// Queries containing a path separator, like `./script.sh`, are explicitly relative and
+    /// aren't affected.
+    pub fn refuse_relative_path_entries(mut self, refuse: bool) -> Self {
+        self.refuse_relative_path_entries = refuse;
+        self
+    }
+
+    /// Changes the extensions tried on Windows for this query, instead of only using those in
+    /// `PATHEXT` as returned by [`Sys::env_windows_path_ext`]. Has no effect when
+    /// [`Sys::is_windows`] is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use which::{PathExtensions, WhichConfig};
+    ///
+    /// let script = WhichConfig::new()
+    ///     .binary_name("build".into())
+    ///     .path_extensions(PathExtensions::Append(vec![".PS1".into()]))
+    ///     .first_result();
+    /// # }
+    /// ```
+    pub fn path_extensions(mut self, path_extensions: PathExtensions) -> Self {
+        self.path_extensions = Some(path_extensions);
         self
     }
 
@@ -439,45 +1001,200 @@ impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
     ///     .collect::<Vec<_>>();
     /// # }
     /// ```
-    pub fn nonfatal_error_handler<NewF>(self, handler: NewF) -> WhichConfig<TSys, NewF> {
+    pub fn nonfatal_error_handler<NewF>(self, handler: NewF) -> WhichConfig<TSys, NewF, C, K, M> {
         WhichConfig {
-            custom_path_list: self.custom_path_list,
+            path_list: self.path_list,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            path_extensions: self.path_extensions,
+            path_list_edits: self.path_list_edits,
             cwd: self.cwd,
             binary_name: self.binary_name,
             nonfatal_error_handler: handler,
+            filter: self.filter,
+            checker: self.checker,
             #[cfg(feature = "regex")]
             regex: self.regex,
+            #[cfg(feature = "regex")]
+            regex_options: self.regex_options,
+            glob: self.glob,
+            mode: self.mode,
             sys: self.sys,
         }
     }
 
+    /// Sets a closure that decides whether a match is accepted. You can also pass in other types
+    /// that implement [`CandidateFilter`].
+    ///
+    /// The filter only sees paths that already passed the usual checks. A rejected path is
+    /// passed to the [`NonFatalErrorHandler`] as [`NonFatalError::RejectedByFilter`], or
+    /// reported as [`CandidateOutcome::Filtered`] by [`explain`](Self::explain), and the search
+    /// continues with the next candidate.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// # use which::WhichConfig;
+    /// // Skip binaries installed by a project's JavaScript dependencies.
+    /// let tsc = WhichConfig::new()
+    ///     .binary_name("tsc".into())
+    ///     .filter(|path: &std::path::Path| {
+    ///         !path.components().any(|c| c.as_os_str() == "node_modules")
+    ///     })
+    ///     .first_result();
+    /// # }
+    /// ```
+    pub fn filter<NewC>(self, filter: NewC) -> WhichConfig<TSys, F, NewC, K, M> {
+        WhichConfig {
+            path_list: self.path_list,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            path_extensions: self.path_extensions,
+            path_list_edits: self.path_list_edits,
+            cwd: self.cwd,
+            binary_name: self.binary_name,
+            nonfatal_error_handler: self.nonfatal_error_handler,
+            filter,
+            checker: self.checker,
+            #[cfg(feature = "regex")]
+            regex: self.regex,
+            #[cfg(feature = "regex")]
+            regex_options: self.regex_options,
+            glob: self.glob,
+            mode: self.mode,
+            sys: self.sys,
+        }
+    }
+
+    /// Sets the [`Checker`] that decides whether a candidate is a match, instead of
+    /// [`DefaultChecker`].
+    ///
+    /// Unlike a [`filter`](Self::filter), the checker replaces the usual checks, so it also
+    /// decides whether the candidate needs to exist. A candidate it rejects is reported by
+    /// [`explain`](Self::explain) with the outcome it returned.
+    pub fn checker<NewK>(self, checker: NewK) -> WhichConfig<TSys, F, C, NewK, M> {
+        WhichConfig {
+            path_list: self.path_list,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            path_extensions: self.path_extensions,
+            path_list_edits: self.path_list_edits,
+            cwd: self.cwd,
+            binary_name: self.binary_name,
+            nonfatal_error_handler: self.nonfatal_error_handler,
+            filter: self.filter,
+            checker,
+            #[cfg(feature = "regex")]
+            regex: self.regex,
+            #[cfg(feature = "regex")]
+            regex_options: self.regex_options,
+            glob: self.glob,
+            mode: self.mode,
+            sys: self.sys,
+        }
+    }
+
+    /// Looks for ordinary files rather than executables: uses [`FileChecker`] instead of
+    /// [`DefaultChecker`] and doesn't try `PATHEXT` extensions on Windows.
+    ///
+    /// Equivalent to `.checker(FileChecker).path_extensions(PathExtensions::Clear)`.
+    pub fn files(self) -> WhichConfig<TSys, F, C, FileChecker, M> {
+        self.path_extensions(PathExtensions::Clear)
+            .checker(FileChecker)
+    }
+
+    /// Runs a regex or glob search.
+    fn find_matching<T: NameMatcher>(
+        self,
+        matcher: T,
+        pattern: String,
+        options: MatchOptions,
+    ) -> Result<WhichFindMatchIter<TSys, T, F, C, K>> {
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve_for_pattern(&self.sys);
+        Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker)
+            .find_matching(
+                matcher,
+                Some(pattern.into()),
+                paths,
+                cwd,
+                options,
+                self.nonfatal_error_handler,
+            )
+    }
+
     /// Finishes configuring, runs the query and returns the first result.
-    pub fn first_result(self) -> Result<path::PathBuf> {
-        self.all_results()
-            .and_then(|mut i| i.next().ok_or(Error::CannotFindBinaryPath))
+    pub fn first_result(mut self) -> Result<path::PathBuf>
+    where
+        M: SearchMode<TSys, K>,
+    {
+        #[cfg(feature = "regex")]
+        if let Some((regex, pattern)) = self.regex.take() {
+            let options = self.regex_options;
+            return self.find_matching(regex, pattern, options)?.first();
+        }
+        if let Some(pattern) = self.glob.take() {
+            let glob = Glob::new(&self.sys, &pattern);
+            return self
+                .find_matching(glob, pattern, MatchOptions::default())?
+                .first();
+        }
+
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve(&self.sys);
+
+        Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker)
+            .spawner(self.mode.spawner())
+            .find_first(
+                self.binary_name.expect(
+                    "binary_name not set! You must set binary_name, regex or glob before searching!",
+                ),
+                paths,
+                cwd,
+                self.nonfatal_error_handler,
+            )
     }
 
     /// Finishes configuring, runs the query and returns all results.
-    pub fn all_results(self) -> Result<impl Iterator<Item = path::PathBuf> + 'a> {
-        let paths = self.custom_path_list.or_else(|| self.sys.env_path());
-
+    pub fn all_results(mut self) -> Result<impl Iterator<Item = path::PathBuf> + 'a>
+    where
+        M: SearchMode<TSys, K>,
+    {
         #[cfg(feature = "regex")]
-        if let Some(regex) = self.regex {
-            return Finder::new(self.sys)
-                .find_re(regex, paths, self.nonfatal_error_handler)
+        if let Some((regex, pattern)) = self.regex.take() {
+            let options = self.regex_options;
+            return self
+                .find_matching(regex, pattern, options)
+                .map(|i| Box::new(i) as Box<dyn Iterator<Item = path::PathBuf> + 'a>);
+        }
+        if let Some(pattern) = self.glob.take() {
+            let glob = Glob::new(&self.sys, &pattern);
+            return self
+                .find_matching(glob, pattern, MatchOptions::default())
                 .map(|i| Box::new(i) as Box<dyn Iterator<Item = path::PathBuf> + 'a>);
         }
 
-        let cwd = match self.cwd {
-            CwdOption::RefuseCwd => None,
-            CwdOption::UseCustomCwd(custom) => Some(custom),
-            CwdOption::UseSysCwd | CwdOption::Unspecified => self.sys.current_dir().ok(),
-        };
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve(&self.sys);
 
         Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker)
+            .spawner(self.mode.spawner())
             .find(
                 self.binary_name.expect(
-                    "binary_name not set! You must set binary_name or regex before searching!",
+                    "binary_name not set! You must set binary_name, regex or glob before searching!",
                 ),
                 paths,
                 cwd,
@@ -485,6 +1202,326 @@ impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> WhichConfig<TSys, F> {
             )
             .map(|i| Box::new(i) as Box<dyn Iterator<Item = path::PathBuf> + 'a>)
     }
+
+    /// Finishes configuring and returns a [`Resolver`] that answers any number of queries for
+    /// a name, without redoing the work that doesn't depend on the name.
+    ///
+    /// The path list, `PATHEXT` and the cwd are read now. `binary_name`, `regex`, `glob` and
+    /// [`parallel`](Self::parallel) are ignored.
+    pub fn resolver(self) -> Resolver<TSys, F, C, K> {
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve(&self.sys);
+        let finder = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker);
+        Resolver::new(finder, paths.as_deref(), cwd, self.nonfatal_error_handler)
+    }
+
+    /// Finishes configuring and returns a [`CachingResolver`], which remembers the results of its
+    /// queries until a searched directory changes, they expire or it's told to
+    /// [`rehash`](CachingResolver::rehash). `binary_name`, `regex`, `glob` and
+    /// [`parallel`](Self::parallel) are ignored.
+    pub fn caching_resolver(self) -> CachingResolver<TSys, F, C, K> {
+        let finder = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker);
+        CachingResolver::new(
+            finder,
+            self.path_list,
+            self.cwd,
+            self.nonfatal_error_handler,
+        )
+    }
+
+    /// Finishes configuring and returns a [`CommandIndex`] saved in the user's cache directory,
+    /// loading it if it was saved before. `binary_name`, `regex`, `glob` and
+    /// [`parallel`](Self::parallel) are ignored.
+    pub fn command_index(self) -> CommandIndex<TSys, F, C, K> {
+        self.command_index_in(None)
+    }
+
+    /// Like [`WhichConfig::command_index`], but saves the index at `location` instead.
+    pub fn command_index_at(
+        self,
+        location: impl Into<path::PathBuf>,
+    ) -> CommandIndex<TSys, F, C, K> {
+        self.command_index_in(Some(location.into()))
+    }
+
+    fn command_index_in(self, location: Option<path::PathBuf>) -> CommandIndex<TSys, F, C, K> {
+        let finder = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker);
+        CommandIndex::new(
+            finder,
+            self.path_list,
+            self.cwd,
+            self.nonfatal_error_handler,
+            location,
+        )
+    }
+
+    /// Looks up every name in `names` at once, returning all results for each of them, in
+    /// search order. Names that weren't found map to an empty list.
+    ///
+    /// Unlike running a query per name, each directory of the path list is read once with
+    /// [`Sys::read_dir`] and only the entries with a wanted name are checked. A directory listed
+    /// more than once is only searched the first time. `binary_name`, `regex` and `glob` are
+    /// ignored. Names containing a path separator aren't looked up in the path list but checked
+    /// as given, relative to the cwd, without trying `PATHEXT` extensions.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// # use which::WhichConfig;
+    /// let tools = WhichConfig::new().resolve_many(["cc", "make", "pkg-config"]).unwrap();
+    /// for (name, paths) in &tools {
+    ///     match paths.first() {
+    ///         Some(path) => println!("{}: {}", name.to_string_lossy(), path.display()),
+    ///         None => println!("{}: not found", name.to_string_lossy()),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn resolve_many<I, T>(self, names: I) -> Result<BTreeMap<OsString, Vec<path::PathBuf>>>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve(&self.sys);
+        let finder = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker);
+        many::resolve_many(
+            finder,
+            names.into_iter().map(Into::into).collect(),
+            paths,
+            cwd,
+            self.nonfatal_error_handler,
+        )
+    }
+
+    /// Finishes configuring, runs the query and returns the first result along with every later
+    /// result it shadows.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// # use which::WhichConfig;
+    /// if let Ok(shadowing) = WhichConfig::new().binary_name("python3".into()).shadowing() {
+    ///     for hidden in &shadowing.shadowed {
+    ///         println!("{} shadows {}", shadowing.active.display(), hidden.display());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `binary_name` wasn't set this will panic.
+    pub fn shadowing(self) -> Result<Shadowing> {
+        let name = self.binary_name.clone().expect(
+            "binary_name not set! You must set binary_name before looking for shadowed binaries!",
+        );
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve(&self.sys);
+        let (active, rest) = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker)
+            .find_first_and_rest(&name, paths, cwd, self.nonfatal_error_handler)?;
+        Ok(Shadowing {
+            name,
+            active,
+            shadowed: rest.collect(),
+        })
+    }
+
+    /// Scans every directory in the path list and returns each command name that resolves in
+    /// more than one directory, sorted by name.
+    ///
+    /// `binary_name`, `regex` and the current working directory are ignored. Entries that aren't
+    /// executable are skipped, and on Windows only files without an extension or with an extension
+    /// listed in `PATHEXT` are considered, grouped by name without that extension.
+    pub fn all_shadowed(self) -> Result<Vec<Shadowing>> {
+        let paths = self.path_list.resolve(&self.sys);
+        let finder = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker);
+        shadow::all_shadowed(finder, paths, self.nonfatal_error_handler)
+    }
+
+    /// Lists the executables on the path list with names close to `binary_name`, for "did you
+    /// mean" messages when a lookup fails.
+    ///
+    /// Names are compared by edit distance, counting insertions, deletions, substitutions and
+    /// swaps of adjacent characters, and those more than a third of the length of `binary_name`
+    /// away (but at least one) are left out. Results are sorted by distance, then by the order
+    /// of their directories in the path list. On Windows names are compared case insensitively
+    /// and without their `PATHEXT` extension. Only the first match for each name is reported.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// # use which::WhichConfig;
+    /// let config = || WhichConfig::new().binary_name("gti".into());
+    /// if let Err(e) = config().first_result() {
+    ///     eprintln!("{e}");
+    ///     if let Some(s) = config().suggestions().unwrap_or_default().first() {
+    ///         eprintln!("did you mean `{}`?", s.name.to_string_lossy());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `binary_name` wasn't set this will panic.
+    pub fn suggestions(self) -> Result<Vec<Suggestion>> {
+        let name = self.binary_name.expect(
+            "binary_name not set! You must set binary_name before looking for suggestions!",
+        );
+        let paths = self.path_list.resolve(&self.sys);
+        let finder = Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker);
+        suggest::suggestions(finder, &name, paths, self.nonfatal_error_handler)
+    }
+
+    /// Diagnoses problems with the entries of the path list itself, rather than running a query.
+    ///
+    /// Reports empty, relative and duplicate entries, entries starting with `~`, entries that
+    /// don't exist or aren't directories, and directories that are group or world writable. Each
+    /// [`PathDiagnostic`] has a [`Severity`].
+    pub fn lint_path(self) -> Result<Vec<PathDiagnostic>> {
+        let paths = self.path_list.resolve(&self.sys);
+        lint::lint_path(self.sys, paths)
+    }
+
+    /// Finishes configuring, runs the query and reports every candidate path that was probed,
+    /// along with why it was accepted or rejected.
+    ///
+    /// I/O errors encountered while checking candidates are recorded in the returned
+    /// [`Explanation`] instead of being passed to the [`NonFatalErrorHandler`].
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// # use which::WhichConfig;
+    /// let explanation = WhichConfig::new()
+    ///     .binary_name("tar".into())
+    ///     .explain()
+    ///     .unwrap();
+    ///
+    /// for candidate in &explanation.candidates {
+    ///     println!("{}: {:?}", candidate.path.display(), candidate.outcome);
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `binary_name` wasn't set, or a `regex` or `glob` was set, this will panic.
+    pub fn explain(self) -> Result<Explanation> {
+        #[cfg(feature = "regex")]
+        if self.regex.is_some() {
+            panic!("which can't explain a regex search!");
+        }
+        if self.glob.is_some() {
+            panic!("which can't explain a glob search!");
+        }
+        let paths = self.path_list.resolve(&self.sys);
+        let cwd = self.cwd.resolve(&self.sys);
+
+        Finder::new(self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions)
+            .path_list_edits(self.path_list_edits)
+            .filter(self.filter)
+            .checker(self.checker)
+            .explain(
+                self.binary_name
+                    .expect("binary_name not set! You must set binary_name before explaining!"),
+                paths,
+                cwd,
+                self.nonfatal_error_handler,
+            )
+    }
+}
+
+impl<TSys: Sys, F, C, K, M> WhichConfig<TSys, F, C, K, M> {
+    /// Checks candidates on up to `threads` threads at once instead of one after another, so a
+    /// slow directory, e.g. on a network file system, doesn't hold up the others.
+    ///
+    /// Results still come in search order, and the filter and the [`NonFatalErrorHandler`] are
+    /// still called in order on the calling thread. Once a candidate matches, no later one is
+    /// checked until the search gets past it. A result is returned as soon as it and every
+    /// candidate before it are checked, and checks still running then finish in the background.
+    ///
+    /// Every thread checks with its own clone of the [`Sys`] and the [`Checker`], so
+    /// [`first_result`](Self::first_result) and [`all_results`](Self::all_results) need them to
+    /// be `Clone`, `Send` and `'static`, see [`SearchMode`]. Only those two run in parallel,
+    /// and only for a `binary_name`. The [`Resolver`], [`CachingResolver`] and [`CommandIndex`]
+    /// built from this config ignore it.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "real-sys")]
+    /// # {
+    /// use std::num::NonZeroUsize;
+    /// use which::WhichConfig;
+    ///
+    /// let cargo = WhichConfig::new()
+    ///     .binary_name("cargo".into())
+    ///     .parallel(NonZeroUsize::new(8).unwrap())
+    ///     .first_result();
+    /// # }
+    /// ```
+    pub fn parallel(self, threads: NonZeroUsize) -> WhichConfig<TSys, F, C, K, Parallel> {
+        WhichConfig {
+            path_list: self.path_list,
+            refuse_relative_path_entries: self.refuse_relative_path_entries,
+            path_extensions: self.path_extensions,
+            path_list_edits: self.path_list_edits,
+            cwd: self.cwd,
+            binary_name: self.binary_name,
+            nonfatal_error_handler: self.nonfatal_error_handler,
+            filter: self.filter,
+            checker: self.checker,
+            #[cfg(feature = "regex")]
+            regex: self.regex,
+            #[cfg(feature = "regex")]
+            regex_options: self.regex_options,
+            glob: self.glob,
+            mode: Parallel(threads),
+            sys: self.sys,
+        }
+    }
 }
 
 /// An owned, immutable wrapper around a `PathBuf` containing the path of an executable.
@@ -628,7 +1665,7 @@ impl CanonicalPath {
             .and_then(|p| {
                 sys::RealSys
                     .canonicalize(&p)
-                    .map_err(|_| Error::CannotCanonicalize)
+                    .map_err(|source| Error::CannotCanonicalize { path: p, source })
             })
             .map(|inner| CanonicalPath { inner })
     }
@@ -644,7 +1681,10 @@ impl CanonicalPath {
             inner.map(|inner| {
                 sys::RealSys
                     .canonicalize(&inner)
-                    .map_err(|_| Error::CannotCanonicalize)
+                    .map_err(|source| Error::CannotCanonicalize {
+                        path: inner,
+                        source,
+                    })
                     .map(|inner| CanonicalPath { inner })
             })
         })
@@ -665,7 +1705,7 @@ impl CanonicalPath {
             .and_then(|p| {
                 sys::RealSys
                     .canonicalize(&p)
-                    .map_err(|_| Error::CannotCanonicalize)
+                    .map_err(|source| Error::CannotCanonicalize { path: p, source })
             })
             .map(|inner| CanonicalPath { inner })
     }
@@ -689,7 +1729,10 @@ impl CanonicalPath {
             inner.map(|inner| {
                 sys::RealSys
                     .canonicalize(&inner)
-                    .map_err(|_| Error::CannotCanonicalize)
+                    .map_err(|source| Error::CannotCanonicalize {
+                        path: inner,
+                        source,
+                    })
                     .map(|inner| CanonicalPath { inner })
             })
         })
@@ -0,0 +1,341 @@
+mod elf;
+mod ld_so;
+
+use crate::checker::{is_valid, FileChecker};
+#[cfg(feature = "real-sys")]
+use crate::sys::RealSys;
+use crate::sys::Sys;
+use crate::{Error, NonFatalError, NonFatalErrorHandler, Noop, Result, SearchContext};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::vec;
+
+const LD_SO_CACHE: &str = "/etc/ld.so.cache";
+const LD_SO_CONF: &str = "/etc/ld.so.conf";
+/// The running program, whose class and machine are used when no object is given.
+const SELF_EXE: &str = "/proc/self/exe";
+
+/// Finds the shared library the dynamic loader would load for a name such as `libssl.so.3`,
+/// following the search order of glibc's `ld.so` on Linux.
+///
+/// The directories are searched in this order:
+///
+/// 1. `DT_RPATH` of the [`object`](Self::object) loading the library, unless it also has a
+///    `DT_RUNPATH`.
+/// 2. The `LD_LIBRARY_PATH` environment variable.
+/// 3. `DT_RUNPATH` of the object.
+/// 4. The libraries listed in `/etc/ld.so.cache`.
+/// 5. The default directories: `/lib64` and `/usr/lib64` for 64-bit objects, or `/lib` and
+///    `/usr/lib` otherwise.
+///
+/// `$ORIGIN` and `$LIB` are expanded in `DT_RPATH` and `DT_RUNPATH`. If the name contains a `/`
+/// it's used as is, without searching. Libraries built for a different ELF class or machine
+/// than the object, or the running program read from `/proc/self/exe` when no object is given,
+/// are skipped, like the loader does. That includes entries of `/etc/ld.so.cache` whose flags
+/// say so. When neither can be read, nothing is skipped.
+///
+/// Everything is read through [`Sys`], including the ELF objects, so lookups can be tested
+/// without a real system.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "real-sys")]
+/// # {
+/// use which::LibraryConfig;
+///
+/// if let Ok(libssl) = LibraryConfig::new()
+///     .name("libssl.so.3".into())
+///     .object("/usr/bin/openssl".into())
+///     .first_result()
+/// {
+///     println!("openssl loads {}", libssl.display());
+/// }
+/// # }
+/// ```
+pub struct LibraryConfig<TSys: Sys, F = Noop> {
+    name: Option<OsString>,
+    object: Option<PathBuf>,
+    ld_so_conf_fallback: bool,
+    nonfatal_error_handler: F,
+    sys: TSys,
+}
+
+#[cfg(feature = "real-sys")]
+impl<F: Default> Default for LibraryConfig<&RealSys, F> {
+    fn default() -> Self {
+        Self {
+            name: None,
+            object: None,
+            ld_so_conf_fallback: false,
+            nonfatal_error_handler: F::default(),
+            sys: &RealSys,
+        }
+    }
+}
+
+#[cfg(feature = "real-sys")]
+impl LibraryConfig<&RealSys, Noop> {
+    pub fn new() -> Self {
+        Self::new_with_sys(&RealSys)
+    }
+}
+
+impl<TSys: Sys> LibraryConfig<TSys, Noop> {
+    /// Creates a new `LibraryConfig` with the given `sys::Sys`.
+    pub fn new_with_sys(sys: TSys) -> Self {
+        Self {
+            name: None,
+            object: None,
+            ld_so_conf_fallback: false,
+            nonfatal_error_handler: Noop,
+            sys,
+        }
+    }
+}
+
+impl<'a, TSys: Sys + 'a, F: NonFatalErrorHandler + 'a> LibraryConfig<TSys, F> {
+    /// The name of the library to find, as it appears in `DT_NEEDED`, e.g. `libssl.so.3`.
+    pub fn name(mut self, name: OsString) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// The ELF executable or library that loads the library. Its `DT_RPATH` and `DT_RUNPATH`
+    /// are searched, and libraries it couldn't load are skipped, instead of the ones the running
+    /// program couldn't load.
+    pub fn object(mut self, object: PathBuf) -> Self {
+        self.object = Some(object);
+        self
+    }
+
+    /// When `/etc/ld.so.cache` can't be read, search the directories listed in `/etc/ld.so.conf`
+    /// and the files it includes instead. `false` by default.
+    ///
+    /// The loader itself never reads `ld.so.conf`, it only finds libraries in those directories
+    /// once `ldconfig` has added them to the cache, so this gives results the loader wouldn't.
+    /// It's meant for systems or images where the cache hasn't been built yet.
+    pub fn ld_so_conf_fallback(mut self, fallback: bool) -> Self {
+        self.ld_so_conf_fallback = fallback;
+        self
+    }
+
+    /// Sets a closure that will receive non-fatal errors. You can also pass in other types
+    /// that implement [`NonFatalErrorHandler`].
+    pub fn nonfatal_error_handler<NewF>(self, handler: NewF) -> LibraryConfig<TSys, NewF> {
+        LibraryConfig {
+            name: self.name,
+            object: self.object,
+            ld_so_conf_fallback: self.ld_so_conf_fallback,
+            nonfatal_error_handler: handler,
+            sys: self.sys,
+        }
+    }
+
+    /// Finishes configuring, runs the query and returns the first result.
+    ///
+    /// # Panics
+    ///
+    /// If `name` wasn't set this will panic.
+    pub fn first_result(self) -> Result<PathBuf> {
+        let query = self.name.clone();
+        let mut iter = self.iter()?;
+        iter.next().ok_or_else(|| {
+            let mut paths = Vec::<PathBuf>::new();
+            for dir in iter.searched.iter().filter_map(|p| p.parent()) {
+                if !paths.iter().any(|p| p == dir) {
+                    paths.push(dir.to_path_buf());
+                }
+            }
+            Error::CannotFindBinaryPath(SearchContext {
+                query,
+                paths,
+                cwd: None,
+            })
+        })
+    }
+
+    /// Finishes configuring, runs the query and returns all results, in the order the loader
+    /// would try them.
+    ///
+    /// # Panics
+    ///
+    /// If `name` wasn't set this will panic.
+    pub fn all_results(self) -> Result<impl Iterator<Item = PathBuf> + 'a> {
+        self.iter()
+    }
+
+    fn iter(self) -> Result<LibraryIter<TSys, F>> {
+        let name = self
+            .name
+            .expect("name not set! You must set name before searching!");
+        let object = match &self.object {
+            Some(path) => Some(
+                self.sys
+                    .read_file(path)
+                    .and_then(|data| elf::parse(&data))
+                    .map_err(|source| Error::CannotReadObject {
+                        path: path.clone(),
+                        source,
+                    })?,
+            ),
+            None => None,
+        };
+        let header = match &object {
+            Some(object) => Some(object.header),
+            None => self
+                .sys
+                .read_file(Path::new(SELF_EXE))
+                .and_then(|data| elf::parse_header(&data))
+                .ok(),
+        };
+        let mut nonfatal_error_handler = self.nonfatal_error_handler;
+
+        let candidates = if name.to_string_lossy().contains('/') {
+            vec![PathBuf::from(&name)]
+        } else {
+            let is_64 = header.map_or(cfg!(target_pointer_width = "64"), |h| h.is_64);
+            // The loader uses the directory the object is really in, after resolving symlinks.
+            let origin = self.object.as_ref().and_then(|path| {
+                let path = self.sys.canonicalize(path).unwrap_or_else(|_| path.clone());
+                path.parent().map(Path::to_path_buf)
+            });
+            let dynamic_dirs = |list: &Option<String>| -> Vec<PathBuf> {
+                list.iter()
+                    .flat_map(|list| split_list(list))
+                    .map(|dir| PathBuf::from(expand(dir, origin.as_deref(), is_64)))
+                    .collect()
+            };
+
+            let mut dirs = Vec::new();
+            if let Some(object) = &object {
+                if object.runpath.is_none() {
+                    dirs.extend(dynamic_dirs(&object.rpath));
+                }
+            }
+            if let Some(list) = self
+                .sys
+                .env_var_os("LD_LIBRARY_PATH".as_ref())
+                .filter(|list| !list.is_empty())
+            {
+                // An empty entry means the current directory.
+                dirs.extend(
+                    split_list(&list.to_string_lossy())
+                        .map(|dir| PathBuf::from(if dir.is_empty() { "." } else { dir })),
+                );
+            }
+            if let Some(object) = &object {
+                dirs.extend(dynamic_dirs(&object.runpath));
+            }
+            let mut candidates: Vec<PathBuf> = dirs.iter().map(|dir| dir.join(&name)).collect();
+
+            let cache = self
+                .sys
+                .read_file(Path::new(LD_SO_CACHE))
+                .and_then(|data| ld_so::lookup_cache(&data, &name, header));
+            match cache {
+                Ok(paths) => candidates.extend(paths),
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        nonfatal_error_handler.handle(NonFatalError::Io(e));
+                    }
+                    if self.ld_so_conf_fallback {
+                        let conf = ld_so::conf_dirs(
+                            &self.sys,
+                            Path::new(LD_SO_CONF),
+                            &mut nonfatal_error_handler,
+                        );
+                        candidates.extend(conf.iter().map(|dir| dir.join(&name)));
+                    }
+                }
+            }
+
+            let default_dirs: &[&str] = if is_64 {
+                &["/lib64", "/usr/lib64"]
+            } else {
+                &["/lib", "/usr/lib"]
+            };
+            candidates.extend(default_dirs.iter().map(|dir| Path::new(dir).join(&name)));
+            candidates
+        };
+
+        let mut unique = Vec::<PathBuf>::with_capacity(candidates.len());
+        for candidate in candidates {
+            if !unique.contains(&candidate) {
+                unique.push(candidate);
+            }
+        }
+        Ok(LibraryIter {
+            sys: self.sys,
+            header,
+            searched: unique.clone(),
+            candidates: unique.into_iter(),
+            nonfatal_error_handler,
+        })
+    }
+}
+
+struct LibraryIter<TSys: Sys, F: NonFatalErrorHandler> {
+    sys: TSys,
+    /// The header of the loading object or the running program, which libraries must match.
+    header: Option<elf::Header>,
+    candidates: vec::IntoIter<PathBuf>,
+    /// Every candidate, to describe the search if nothing was found.
+    searched: Vec<PathBuf>,
+    nonfatal_error_handler: F,
+}
+
+impl<TSys: Sys, F: NonFatalErrorHandler> Iterator for LibraryIter<TSys, F> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        for candidate in &mut self.candidates {
+            if !is_valid(
+                &FileChecker,
+                &self.sys,
+                &candidate,
+                &mut self.nonfatal_error_handler,
+            ) {
+                continue;
+            }
+            let Some(header) = self.header else {
+                return Some(candidate);
+            };
+            match self
+                .sys
+                .read_file(&candidate)
+                .and_then(|data| elf::parse_header(&data))
+            {
+                Ok(found) if found == header => return Some(candidate),
+                Ok(_) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        "skipping {}, built for a different class or machine",
+                        candidate.display()
+                    );
+                }
+                Err(e) => self.nonfatal_error_handler.handle(NonFatalError::Io(e)),
+            }
+        }
+        None
+    }
+}
+
+/// Splits a `DT_RPATH`, `DT_RUNPATH` or `LD_LIBRARY_PATH` list. The loader accepts both `:` and
+/// `;` as separators.
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split([':', ';'])
+}
+
+/// Expands the `$ORIGIN` and `$LIB` dynamic string tokens, also written `${ORIGIN}` and `${LIB}`.
+fn expand(dir: &str, origin: Option<&Path>, is_64: bool) -> String {
+    let lib = if is_64 { "lib64" } else { "lib" };
+    let mut dir = dir.replace("${LIB}", lib).replace("$LIB", lib);
+    if let Some(origin) = origin {
+        let origin = origin.to_string_lossy();
+        dir = dir
+            .replace("${ORIGIN}", &origin)
+            .replace("$ORIGIN", &origin);
+    }
+    dir
+}
@@ -0,0 +1,202 @@
+use std::io;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const DT_NULL: u64 = 0;
+const DT_STRTAB: u64 = 5;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+/// What the dynamic loader needs to know about an ELF object to find its dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Elf {
+    pub header: Header,
+    /// `DT_RPATH`, only used when there's no `DT_RUNPATH`.
+    pub rpath: Option<String>,
+    pub runpath: Option<String>,
+}
+
+/// The parts of the ELF header that decide whether the loader can use a library at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub is_64: bool,
+    pub machine: u16,
+}
+
+/// Bounds checked, endian aware reads from the contents of an ELF file.
+struct Reader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn new(data: &[u8]) -> io::Result<Reader<'_>> {
+        if data.get(..4) != Some(b"\x7fELF") {
+            return Err(invalid("not an ELF file"));
+        }
+        let is_64 = match data.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err(invalid("unknown ELF class")),
+        };
+        let big_endian = match data.get(5) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err(invalid("unknown ELF data encoding")),
+        };
+        Ok(Reader {
+            data,
+            is_64,
+            big_endian,
+        })
+    }
+
+    fn bytes<const N: usize>(&self, offset: u64) -> io::Result<[u8; N]> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.data.get(start..start.checked_add(N)?))
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("truncated ELF file"))
+    }
+
+    fn u16(&self, offset: u64) -> io::Result<u16> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> io::Result<u32> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> io::Result<u64> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+
+    /// Reads an address or offset, which is 32 or 64 bits wide depending on the class.
+    fn word(&self, offset: u64) -> io::Result<u64> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    fn c_str(&self, offset: u64) -> io::Result<String> {
+        let rest = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.data.get(start..))
+            .ok_or_else(|| invalid("string table out of bounds"))?;
+        let end = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("unterminated string"))?;
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    }
+}
+
+/// Reads the class and machine of an ELF file.
+pub(crate) fn parse_header(data: &[u8]) -> io::Result<Header> {
+    let reader = Reader::new(data)?;
+    Ok(Header {
+        is_64: reader.is_64,
+        machine: reader.u16(18)?,
+    })
+}
+
+/// Reads the header and the `DT_RPATH` and `DT_RUNPATH` entries of an ELF file.
+pub(crate) fn parse(data: &[u8]) -> io::Result<Elf> {
+    let reader = Reader::new(data)?;
+    let header = parse_header(data)?;
+    let (phoff, phentsize, phnum) = if reader.is_64 {
+        (reader.u64(32)?, reader.u16(54)?, reader.u16(56)?)
+    } else {
+        (u64::from(reader.u32(28)?), reader.u16(42)?, reader.u16(44)?)
+    };
+
+    // (virtual address, file offset, size in the file) of each loaded segment.
+    let mut loads = Vec::new();
+    let mut dynamic = None;
+    for i in 0..u64::from(phnum) {
+        let ph = phoff.saturating_add(i * u64::from(phentsize));
+        let (offset, vaddr, filesz) = if reader.is_64 {
+            (
+                reader.u64(ph.saturating_add(8))?,
+                reader.u64(ph.saturating_add(16))?,
+                reader.u64(ph.saturating_add(32))?,
+            )
+        } else {
+            (
+                u64::from(reader.u32(ph.saturating_add(4))?),
+                u64::from(reader.u32(ph.saturating_add(8))?),
+                u64::from(reader.u32(ph.saturating_add(16))?),
+            )
+        };
+        match reader.u32(ph)? {
+            PT_LOAD => loads.push((vaddr, offset, filesz)),
+            PT_DYNAMIC => dynamic = Some((offset, filesz)),
+            _ => {}
+        }
+    }
+
+    let mut elf = Elf {
+        header,
+        rpath: None,
+        runpath: None,
+    };
+    // A statically linked object has no dependencies to look up.
+    let Some((offset, size)) = dynamic else {
+        return Ok(elf);
+    };
+    let entry_size = if reader.is_64 { 16 } else { 8 };
+    let mut strtab = None;
+    let mut rpath = None;
+    let mut runpath = None;
+    for i in 0..size / entry_size {
+        let entry = offset.saturating_add(i * entry_size);
+        let tag = reader.word(entry)?;
+        let value = reader.word(entry.saturating_add(entry_size / 2))?;
+        match tag {
+            DT_NULL => break,
+            DT_STRTAB => strtab = Some(value),
+            DT_RPATH => rpath = Some(value),
+            DT_RUNPATH => runpath = Some(value),
+            _ => {}
+        }
+    }
+    if rpath.is_none() && runpath.is_none() {
+        return Ok(elf);
+    }
+
+    let strtab = strtab.ok_or_else(|| invalid("missing DT_STRTAB"))?;
+    let strtab = loads
+        .iter()
+        .find(|(vaddr, _, filesz)| (*vaddr..vaddr.saturating_add(*filesz)).contains(&strtab))
+        .map(|(vaddr, offset, _)| offset.saturating_add(strtab - vaddr))
+        .ok_or_else(|| invalid("DT_STRTAB is outside of every loaded segment"))?;
+    elf.rpath = rpath
+        .map(|r| reader.c_str(strtab.saturating_add(r)))
+        .transpose()?;
+    elf.runpath = runpath
+        .map(|r| reader.c_str(strtab.saturating_add(r)))
+        .transpose()?;
+    Ok(elf)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,240 @@
+use super::elf::Header;
+use crate::helper::wildcard_match;
+use crate::sys::{Sys, SysReadDirEntry};
+use crate::{NonFatalError, NonFatalErrorHandler};
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const OLD_MAGIC: &[u8] = b"ld.so-1.7.0";
+const NEW_MAGIC: &[u8] = b"glibc-ld.so.cache1.1";
+/// Size of the header of the new format, see `struct cache_file_new` in glibc.
+const NEW_HEADER_SIZE: usize = 48;
+const NEW_ENTRY_SIZE: usize = 24;
+const OLD_ENTRY_SIZE: usize = 12;
+/// How deeply `include` directives may nest, to stop include loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+/// The flags of an entry any object can load.
+const FLAG_ELF: u32 = 0x0001;
+
+/// Looks up `name` in the contents of `/etc/ld.so.cache`, returning the path of every entry for
+/// it in the order the loader tries them.
+///
+/// With a `header`, entries whose flags say they were built for a different ELF class or
+/// machine are skipped, like the loader does.
+///
+/// Both the new format and the old one, with or without the new one appended, are understood.
+/// Numbers are in the byte order of the host, like `ldconfig` writes them.
+pub(crate) fn lookup_cache(
+    data: &[u8],
+    name: &OsStr,
+    header: Option<Header>,
+) -> io::Result<Vec<PathBuf>> {
+    let name = name.to_string_lossy();
+    let name = name.as_bytes();
+    let entries = if data.starts_with(NEW_MAGIC) {
+        new_entries(data, 0)?
+    } else if data.starts_with(OLD_MAGIC) {
+        let count = read_u32(data, 12)? as usize;
+        let old_end = count
+            .checked_mul(OLD_ENTRY_SIZE)
+            .and_then(|size| size.checked_add(16))
+            .ok_or_else(|| invalid("ld.so.cache is truncated"))?;
+        // The new format follows the old one, aligned for its 64-bit fields.
+        let new_start = (old_end + 7) & !7;
+        if data
+            .get(new_start..)
+            .is_some_and(|d| d.starts_with(NEW_MAGIC))
+        {
+            new_entries(data, new_start)?
+        } else {
+            (0..count)
+                .map(|i| {
+                    let entry = 16 + i * OLD_ENTRY_SIZE;
+                    Ok((
+                        read_u32(data, entry)?,
+                        old_end + read_u32(data, entry + 4)? as usize,
+                        old_end + read_u32(data, entry + 8)? as usize,
+                    ))
+                })
+                .collect::<io::Result<_>>()?
+        }
+    } else {
+        return Err(invalid("unknown ld.so.cache format"));
+    };
+
+    let ids = header.and_then(cache_ids);
+    let mut paths = Vec::new();
+    for (flags, key, value) in entries {
+        if ids.is_some_and(|ids| flags != FLAG_ELF && !ids.contains(&flags)) {
+            continue;
+        }
+        if read_c_str(data, key)? == name {
+            let path = String::from_utf8_lossy(read_c_str(data, value)?);
+            paths.push(PathBuf::from(path.into_owned()));
+        }
+    }
+    Ok(paths)
+}
+
+/// Returns the flags of the libraries an object with `header` can load, see
+/// `_DL_CACHE_DEFAULT_ID` in glibc, or `None` if that depends on more than the class and machine.
+fn cache_ids(header: Header) -> Option<&'static [u32]> {
+    const EM_SPARC: u16 = 2;
+    const EM_386: u16 = 3;
+    const EM_SPARC32PLUS: u16 = 18;
+    const EM_PPC: u16 = 20;
+    const EM_PPC64: u16 = 21;
+    const EM_S390: u16 = 22;
+    const EM_ARM: u16 = 40;
+    const EM_SPARCV9: u16 = 43;
+    const EM_IA_64: u16 = 50;
+    const EM_X86_64: u16 = 62;
+    const EM_AARCH64: u16 = 183;
+    const EM_RISCV: u16 = 243;
+    const EM_LOONGARCH: u16 = 258;
+
+    Some(match (header.is_64, header.machine) {
+        (false, EM_386 | EM_SPARC | EM_SPARC32PLUS | EM_PPC | EM_S390) => &[0x0003],
+        (true, EM_SPARCV9) => &[0x0103],
+        (true, EM_IA_64) => &[0x0203],
+        (true, EM_X86_64) => &[0x0303],
+        (true, EM_S390) => &[0x0403],
+        (true, EM_PPC64) => &[0x0503],
+        // x32.
+        (false, EM_X86_64) => &[0x0803],
+        // Hard and soft float.
+        (false, EM_ARM) => &[0x0003, 0x0903, 0x0b03],
+        (true, EM_AARCH64) => &[0x0a03],
+        (_, EM_RISCV) => &[0x0f03, 0x1003],
+        (true, EM_LOONGARCH) => &[0x1103, 0x1203],
+        _ => return None,
+    })
+}
+
+/// Returns the flags and the absolute offsets of the key and value of every entry of the new
+/// format starting at `start`. Offsets in the file are relative to `start`.
+fn new_entries(data: &[u8], start: usize) -> io::Result<Vec<(u32, usize, usize)>> {
+    let count = read_u32(data, start + NEW_MAGIC.len())? as usize;
+    (0..count)
+        .map(|i| {
+            let entry = i
+                .checked_mul(NEW_ENTRY_SIZE)
+                .and_then(|offset| offset.checked_add(start + NEW_HEADER_SIZE))
+                .ok_or_else(|| invalid("ld.so.cache is truncated"))?;
+            Ok((
+                read_u32(data, entry)?,
+                start + read_u32(data, entry + 4)? as usize,
+                start + read_u32(data, entry + 8)? as usize,
+            ))
+        })
+        .collect()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_ne_bytes)
+        .ok_or_else(|| invalid("ld.so.cache is truncated"))
+}
+
+fn read_c_str(data: &[u8], offset: usize) -> io::Result<&[u8]> {
+    let rest = data
+        .get(offset..)
+        .ok_or_else(|| invalid("ld.so.cache is truncated"))?;
+    let end = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| invalid("ld.so.cache is truncated"))?;
+    Ok(&rest[..end])
+}
+
+/// Collects the directories listed in an `ld.so.conf` file, following `include` directives.
+/// Files that can't be read are passed to the [`NonFatalErrorHandler`].
+pub(crate) fn conf_dirs<TSys: Sys, F: NonFatalErrorHandler>(
+    sys: TSys,
+    conf: &Path,
+    nonfatal_error_handler: &mut F,
+) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    read_conf(&sys, conf, 0, &mut dirs, nonfatal_error_handler);
+    dirs
+}
+
+fn read_conf<TSys: Sys, F: NonFatalErrorHandler>(
+    sys: &TSys,
+    conf: &Path,
+    depth: usize,
+    dirs: &mut Vec<PathBuf>,
+    nonfatal_error_handler: &mut F,
+) {
+    let contents = match sys.read_file(conf) {
+        Ok(contents) => contents,
+        Err(e) => {
+            nonfatal_error_handler.handle(NonFatalError::Io(e));
+            return;
+        }
+    };
+    let base = conf.parent().unwrap_or(Path::new("/"));
+    for line in String::from_utf8_lossy(&contents).lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None | Some("hwcap") => {}
+            Some("include") if depth < MAX_INCLUDE_DEPTH => {
+                for pattern in words {
+                    for included in glob(sys, &base.join(pattern), nonfatal_error_handler) {
+                        read_conf(sys, &included, depth + 1, dirs, nonfatal_error_handler);
+                    }
+                }
+            }
+            Some("include") => {}
+            Some(_) => {
+                for dir in line.split(|c: char| c == ':' || c == ',' || c.is_whitespace()) {
+                    if !dir.is_empty() {
+                        dirs.push(PathBuf::from(dir));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Expands wildcards in the last component of `pattern`, returning the matches in sorted order.
+/// A pattern without wildcards is returned as is.
+fn glob<TSys: Sys, F: NonFatalErrorHandler>(
+    sys: TSys,
+    pattern: &Path,
+    nonfatal_error_handler: &mut F,
+) -> Vec<PathBuf> {
+    let (Some(dir), Some(file_pattern)) = (pattern.parent(), pattern.file_name()) else {
+        return vec![pattern.to_path_buf()];
+    };
+    let file_pattern = file_pattern.to_string_lossy();
+    if !file_pattern.contains(['*', '?', '[']) {
+        return vec![pattern.to_path_buf()];
+    }
+    let entries = match sys.read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            nonfatal_error_handler.handle(NonFatalError::Io(e));
+            return Vec::new();
+        }
+    };
+    let mut matches = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) if wildcard_match(&file_pattern, &entry.file_name().to_string_lossy()) => {
+                matches.push(entry.path());
+            }
+            Ok(_) => {}
+            Err(e) => nonfatal_error_handler.handle(NonFatalError::Io(e)),
+        }
+    }
+    matches.sort();
+    matches
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
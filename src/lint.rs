@@ -0,0 +1,187 @@
+use crate::finder::{has_tilde, tilde_expansion};
+use crate::sys::{Sys, SysMetadata};
+use crate::{Error, Result};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+/// How serious a [`PathDiagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Harmless, but probably not intended.
+    Info,
+    /// Likely to cause confusing lookups or security problems.
+    Warning,
+    /// The entry is broken or dangerous.
+    Error,
+}
+
+/// A problem found with one entry of a path list by [`WhichConfig::lint_path`](crate::WhichConfig::lint_path).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PathDiagnostic {
+    /// The index of the entry in the path list.
+    pub index: usize,
+    /// The entry as written in the path list.
+    pub entry: PathBuf,
+    /// What is wrong with the entry.
+    pub kind: PathDiagnosticKind,
+}
+
+impl PathDiagnostic {
+    /// How serious this diagnostic is.
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for PathDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "path entry {} `{}`: {}",
+            self.index,
+            self.entry.display(),
+            self.kind
+        )
+    }
+}
+
+/// The kinds of problems [`WhichConfig::lint_path`](crate::WhichConfig::lint_path) looks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathDiagnosticKind {
+    /// The entry is empty. On Unix this searches the current working directory.
+    Empty,
+    /// The entry is a relative path, so what it refers to depends on the current working directory.
+    Relative,
+    /// The entry starts with `~`. `which` expands it, but most other programs, including the
+    /// `exec` family of functions, don't.
+    UnexpandedTilde,
+    /// The entry doesn't exist, or its metadata couldn't be read.
+    Missing,
+    /// The entry exists, but isn't a directory.
+    NotADirectory,
+    /// The entry is identical to an earlier entry.
+    Duplicate {
+        /// The index of the earlier entry.
+        first: usize,
+    },
+    /// The entry resolves to the same directory as an earlier entry once symlinks are resolved.
+    DuplicateAfterCanonicalization {
+        /// The index of the earlier entry.
+        first: usize,
+        /// The directory both entries resolve to.
+        canonical: PathBuf,
+    },
+    /// The directory can be written to by users other than its owner, so they could plant
+    /// executables in it.
+    InsecurePermissions {
+        /// The Unix permission bits of the directory.
+        mode: u32,
+    },
+}
+
+impl PathDiagnosticKind {
+    /// How serious this kind of problem is.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Duplicate { .. } | Self::DuplicateAfterCanonicalization { .. } => Severity::Info,
+            Self::Empty | Self::Relative | Self::UnexpandedTilde | Self::Missing => {
+                Severity::Warning
+            }
+            Self::InsecurePermissions { mode } if mode & 0o002 == 0 => Severity::Warning,
+            Self::NotADirectory | Self::InsecurePermissions { .. } => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for PathDiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty entry, the current directory will be searched"),
+            Self::Relative => write!(f, "relative entry, depends on the current directory"),
+            Self::UnexpandedTilde => write!(f, "contains `~`, which most programs won't expand"),
+            Self::Missing => write!(f, "directory does not exist"),
+            Self::NotADirectory => write!(f, "not a directory"),
+            Self::Duplicate { first } => write!(f, "duplicate of entry {first}"),
+            Self::DuplicateAfterCanonicalization { first, canonical } => write!(
+                f,
+                "same directory as entry {first} ({})",
+                canonical.display()
+            ),
+            Self::InsecurePermissions { mode } => {
+                let who = if mode & 0o002 != 0 { "world" } else { "group" };
+                write!(f, "directory is {who} writable (mode {:o})", mode & 0o7777)
+            }
+        }
+    }
+}
+
+pub fn lint_path<TSys: Sys>(sys: TSys, paths: Option<OsString>) -> Result<Vec<PathDiagnostic>> {
+    let paths =
+        paths.ok_or_else(|| Error::CannotGetCurrentDirAndPathListEmpty(Default::default()))?;
+    let entries = sys.env_split_paths(&paths);
+
+    let mut diagnostics = Vec::new();
+    let mut seen = Vec::<(usize, PathBuf)>::new();
+    let mut seen_canonical = Vec::<(usize, PathBuf)>::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let mut report = |kind| {
+            diagnostics.push(PathDiagnostic {
+                index,
+                entry: entry.clone(),
+                kind,
+            })
+        };
+
+        if entry.as_os_str().is_empty() {
+            // Windows skips empty entries, so they're only a problem elsewhere.
+            if !sys.is_windows() {
+                report(PathDiagnosticKind::Empty);
+            }
+            continue;
+        }
+        if has_tilde(&entry) {
+            report(PathDiagnosticKind::UnexpandedTilde);
+        }
+        let dir = tilde_expansion(&sys, &entry).into_owned();
+        if dir.is_relative() {
+            report(PathDiagnosticKind::Relative);
+            continue;
+        }
+
+        if let Some((first, _)) = seen.iter().find(|(_, d)| *d == dir) {
+            report(PathDiagnosticKind::Duplicate { first: *first });
+            continue;
+        }
+        seen.push((index, dir.clone()));
+
+        let metadata = match sys.metadata(&dir) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                report(PathDiagnosticKind::Missing);
+                continue;
+            }
+        };
+        if !metadata.is_dir() {
+            report(PathDiagnosticKind::NotADirectory);
+            continue;
+        }
+        if let Some(mode) = metadata.unix_mode().filter(|mode| mode & 0o022 != 0) {
+            report(PathDiagnosticKind::InsecurePermissions { mode });
+        }
+
+        if let Ok(canonical) = sys.canonicalize(&dir) {
+            if let Some((first, _)) = seen_canonical.iter().find(|(_, c)| *c == canonical) {
+                report(PathDiagnosticKind::DuplicateAfterCanonicalization {
+                    first: *first,
+                    canonical,
+                });
+            } else {
+                seen_canonical.push((index, canonical));
+            }
+        }
+    }
+    Ok(diagnostics)
+}
@@ -0,0 +1,108 @@
+use crate::checker::is_valid;
+use crate::finder::{path_extensions, Finder, PathExt};
+use crate::scan::{lookup_keys, name_key, path_dirs, scan_entries};
+use crate::sys::Sys;
+use crate::{CandidateFilter, Checker, NonFatalError, NonFatalErrorHandler, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+pub fn resolve_many<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>(
+    mut finder: Finder<TSys, P, K>,
+    names: Vec<OsString>,
+    paths: Option<OsString>,
+    cwd: Option<PathBuf>,
+    mut nonfatal_error_handler: F,
+) -> Result<BTreeMap<OsString, Vec<PathBuf>>> {
+    let is_windows = finder.sys.is_windows();
+
+    let mut results = BTreeMap::<OsString, Vec<PathBuf>>::new();
+    // The names to look up in the path list, by key.
+    let mut wanted = HashMap::<OsString, Vec<OsString>>::new();
+    for name in names {
+        if results.contains_key(&name) {
+            continue;
+        }
+        let found = if Path::new(&name).components().count() > 1 {
+            check_path(
+                &mut finder,
+                &name,
+                cwd.as_deref(),
+                &mut nonfatal_error_handler,
+            )
+        } else {
+            wanted
+                .entry(name_key(is_windows, &name))
+                .or_default()
+                .push(name.clone());
+            Vec::new()
+        };
+        results.insert(name, found);
+    }
+    if wanted.is_empty() {
+        return Ok(results);
+    }
+
+    let dirs = path_dirs(&finder.sys, paths, &finder.path_list_edits)?;
+    let path_extensions = path_extensions(&finder.sys, finder.path_extensions.as_ref());
+    // The keys of the wanted names a file is found by, along with its rank for each.
+    let found_by = |sys: &TSys, file_name: &OsStr| {
+        let mut keys = lookup_keys(sys, file_name, &path_extensions);
+        keys.retain(|(key, _)| wanted.contains_key(key));
+        keys
+    };
+    let commands = scan_entries(
+        &mut finder,
+        &dirs,
+        &mut nonfatal_error_handler,
+        |sys, file_name, _| {
+            if found_by(sys, file_name).is_empty() {
+                None
+            } else {
+                Some((file_name.to_os_string(), 0))
+            }
+        },
+    );
+    let mut found = Vec::new();
+    for command in &commands {
+        for (key, rank) in found_by(&finder.sys, &command.name) {
+            found.push((command.dir_index, rank, key, &command.path));
+        }
+    }
+    found.sort_by_key(|&(dir_index, rank, _, _)| (dir_index, rank));
+    for (_, _, key, path) in found {
+        for name in &wanted[&key] {
+            results
+                .get_mut(name)
+                .expect("every wanted name has an entry")
+                .push(path.clone());
+        }
+    }
+    Ok(results)
+}
+
+/// Checks a name containing a path separator, which isn't looked up in the path list.
+fn check_path<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>(
+    finder: &mut Finder<TSys, P, K>,
+    name: &OsStr,
+    cwd: Option<&Path>,
+    nonfatal_error_handler: &mut F,
+) -> Vec<PathBuf> {
+    let path = match cwd {
+        Some(cwd) => PathBuf::from(name).to_absolute(cwd),
+        None => PathBuf::from(name),
+    };
+    if !is_valid(
+        &finder.checker,
+        &finder.sys,
+        &path,
+        &mut *nonfatal_error_handler,
+    ) {
+        return Vec::new();
+    }
+    if !finder.filter.accept(&path) {
+        nonfatal_error_handler.handle(NonFatalError::RejectedByFilter(path));
+        return Vec::new();
+    }
+    vec![path]
+}
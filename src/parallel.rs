@@ -0,0 +1,209 @@
+use crate::checker::Checker;
+use crate::explain::CandidateOutcome;
+use crate::sys::Sys;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// How [`WhichConfig::first_result`](crate::WhichConfig::first_result) and
+/// [`WhichConfig::all_results`](crate::WhichConfig::all_results) check candidates: one after
+/// another with [`Sequential`], the default, or on several threads with [`Parallel`], set by
+/// [`WhichConfig::parallel`](crate::WhichConfig::parallel).
+///
+/// [`Parallel`] only implements it for a [`Sys`] and a [`Checker`] that are `Clone`, `Send` and
+/// `'static`, as every thread checks with its own copy of them.
+pub trait SearchMode<TSys, K> {
+    #[doc(hidden)]
+    fn spawner(&self) -> Option<Spawner<TSys, K>>;
+}
+
+/// Checks candidates one after another on the calling thread.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sequential;
+
+/// Checks candidates on up to a number of threads, see
+/// [`WhichConfig::parallel`](crate::WhichConfig::parallel).
+#[derive(Clone, Copy, Debug)]
+pub struct Parallel(pub(crate) NonZeroUsize);
+
+impl<TSys, K> SearchMode<TSys, K> for Sequential {
+    fn spawner(&self) -> Option<Spawner<TSys, K>> {
+        None
+    }
+}
+
+impl<TSys, K> SearchMode<TSys, K> for Parallel
+where
+    TSys: Sys + Clone + Send + 'static,
+    K: Checker + Clone + Send + 'static,
+{
+    fn spawner(&self) -> Option<Spawner<TSys, K>> {
+        Some(Spawner {
+            threads: self.0,
+            start: start::<TSys, K>,
+        })
+    }
+}
+
+type StartFn<TSys, K> = fn(&TSys, &K, NonZeroUsize, Arc<[PathBuf]>) -> Pool;
+
+/// Starts the [`Pool`] of a search.
+///
+/// A search can run wherever its [`Sys`] and [`Checker`] can be used, so the function that
+/// moves them to other threads is picked by [`SearchMode`], where the bounds can be checked.
+pub struct Spawner<TSys, K> {
+    threads: NonZeroUsize,
+    start: StartFn<TSys, K>,
+}
+
+impl<TSys, K> Clone for Spawner<TSys, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<TSys, K> Copy for Spawner<TSys, K> {}
+
+impl<TSys, K> Spawner<TSys, K> {
+    /// Starts checking `paths` in the background.
+    pub(crate) fn start(&self, sys: &TSys, checker: &K, paths: Arc<[PathBuf]>) -> Pool {
+        (self.start)(sys, checker, self.threads, paths)
+    }
+}
+
+/// The threads checking the candidates of one search, and the outcomes they sent so far.
+///
+/// Candidates are handed out in search order. A thread doesn't start on a candidate after a
+/// match the search hasn't got past yet, and waits until the search moves on or is dropped
+/// instead. Dropping the pool doesn't wait for the checks that are running.
+pub(crate) struct Pool {
+    shared: Arc<Shared>,
+    receiver: Receiver<(usize, thread::Result<CandidateOutcome>)>,
+    outcomes: Vec<Option<thread::Result<CandidateOutcome>>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+struct State {
+    /// Index of the next candidate to hand out.
+    next: usize,
+    /// Index of the candidate the search is waiting for. Matches before it don't hold up the
+    /// threads anymore.
+    waiting: usize,
+    matches: Vec<usize>,
+    dropped: bool,
+}
+
+impl State {
+    fn is_held_up(&self) -> bool {
+        self.matches
+            .iter()
+            .any(|&i| i >= self.waiting && i < self.next)
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn start<TSys, K>(sys: &TSys, checker: &K, threads: NonZeroUsize, paths: Arc<[PathBuf]>) -> Pool
+where
+    TSys: Sys + Clone + Send + 'static,
+    K: Checker + Clone + Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            next: 0,
+            waiting: 0,
+            matches: Vec::new(),
+            dropped: false,
+        }),
+        wake: Condvar::new(),
+    });
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..threads.get().min(paths.len()) {
+        let (sys, checker) = (sys.clone(), checker.clone());
+        let (shared, paths, sender) = (shared.clone(), paths.clone(), sender.clone());
+        // If no thread can be spawned, the search checks every candidate itself.
+        let _ =
+            thread::Builder::new().spawn(move || work(&sys, &checker, &shared, &paths, &sender));
+    }
+    Pool {
+        shared,
+        receiver,
+        outcomes: paths.iter().map(|_| None).collect(),
+    }
+}
+
+fn work<TSys: Sys, K: Checker>(
+    sys: &TSys,
+    checker: &K,
+    shared: &Shared,
+    paths: &[PathBuf],
+    sender: &Sender<(usize, thread::Result<CandidateOutcome>)>,
+) {
+    loop {
+        let i = {
+            let mut state = shared.lock();
+            loop {
+                if state.dropped || state.next == paths.len() {
+                    return;
+                }
+                if !state.is_held_up() {
+                    state.next += 1;
+                    break state.next - 1;
+                }
+                state = shared
+                    .wake
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        };
+        // A panic is passed on, to be raised on the calling thread if the search gets there.
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| checker.check(sys, &paths[i])));
+        if let Ok(CandidateOutcome::Match) = outcome {
+            shared.lock().matches.push(i);
+        }
+        if sender.send((i, outcome)).is_err() {
+            return;
+        }
+    }
+}
+
+impl Pool {
+    /// Waits for the outcome of candidate `i`, which must not have been taken before.
+    ///
+    /// Returns `None` if no thread is left to check it.
+    pub(crate) fn take(&mut self, i: usize) -> Option<CandidateOutcome> {
+        {
+            let mut state = self.shared.lock();
+            if state.waiting < i {
+                state.waiting = i;
+                self.shared.wake.notify_all();
+            }
+        }
+        while self.outcomes[i].is_none() {
+            let (j, outcome) = self.receiver.recv().ok()?;
+            self.outcomes[j] = Some(outcome);
+        }
+        match self.outcomes[i].take()? {
+            Ok(outcome) => Some(outcome),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.shared.lock().dropped = true;
+        self.shared.wake.notify_all();
+    }
+}
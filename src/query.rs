@@ -0,0 +1,92 @@
+use crate::{Error, Result};
+use std::ffi::OsString;
+use std::path::{self, PathBuf};
+use std::str::FromStr;
+
+/// A `where.exe` style query: a name or wildcard pattern, optionally scoped to a single
+/// directory or to the path list in an environment variable.
+///
+/// | Query | Searches |
+/// |---|---|
+/// | `java` | The system path list |
+/// | `$JAVA_HOME:java*` | The path list in the `JAVA_HOME` environment variable |
+/// | `C:\tools:*.exe` | Only the directory `C:\tools` |
+///
+/// The query is split at its last `:`, so the scope may contain colons itself. A query whose
+/// part after the last `:` contains a path separator, like `C:\tools\java.exe`, isn't scoped.
+/// The pattern is a [`glob`](crate::WhichConfig::glob) if it contains `*`, `?` or `[`, and a
+/// [`binary_name`](crate::WhichConfig::binary_name) otherwise.
+///
+/// # Example
+/// ```
+/// use std::ffi::OsString;
+/// use which::{Query, QueryScope};
+///
+/// let query: Query = "$JAVA_HOME:java*".parse().unwrap();
+/// assert_eq!(query.scope, QueryScope::Var(OsString::from("JAVA_HOME")));
+/// assert_eq!(query.pattern, "java*");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Query {
+    /// Where to search.
+    pub scope: QueryScope,
+    /// The name or wildcard pattern to search for.
+    pub pattern: String,
+}
+
+/// Where a [`Query`] searches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryScope {
+    /// The path list the [`WhichConfig`](crate::WhichConfig) would search anyway.
+    PathList,
+    /// Only this directory.
+    Dir(PathBuf),
+    /// The path list in this environment variable.
+    Var(OsString),
+}
+
+impl Query {
+    /// Parses a query, failing with [`Error::InvalidQuery`] if its scope or pattern is empty.
+    pub fn parse(query: &str) -> Result<Query> {
+        let invalid = || Error::InvalidQuery(query.to_owned());
+        let Some((scope, pattern)) = query
+            .rsplit_once(':')
+            .filter(|(_, pattern)| !pattern.contains(path::is_separator))
+        else {
+            if query.is_empty() {
+                return Err(invalid());
+            }
+            return Ok(Query {
+                scope: QueryScope::PathList,
+                pattern: query.to_owned(),
+            });
+        };
+        if pattern.is_empty() {
+            return Err(invalid());
+        }
+        let scope = match scope.strip_prefix('$') {
+            Some("") => return Err(invalid()),
+            Some(var) => QueryScope::Var(var.into()),
+            None if scope.is_empty() => return Err(invalid()),
+            None => QueryScope::Dir(scope.into()),
+        };
+        Ok(Query {
+            scope,
+            pattern: pattern.to_owned(),
+        })
+    }
+
+    /// Whether the pattern contains wildcards, making this a glob search.
+    pub fn is_glob(&self) -> bool {
+        self.pattern.contains(['*', '?', '['])
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<Query> {
+        Query::parse(query)
+    }
+}
@@ -0,0 +1,67 @@
+use std::ffi::OsStr;
+
+/// A regular expression that regex searches match file names, or full paths, against.
+///
+/// It's implemented for [`regex::Regex`], which can only match names that are valid Unicode,
+/// and for [`regex::bytes::Regex`], which also matches names that aren't. On Unix file names are
+/// arbitrary bytes and are matched as they are; elsewhere invalid sequences are replaced with
+/// `U+FFFD` first.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "real-sys")]
+/// # {
+/// use regex::bytes::Regex;
+///
+/// // Also finds files whose names aren't valid UTF-8.
+/// let scripts: Vec<_> = which::which_re(Regex::new(r"\.sh$").unwrap())
+///     .unwrap()
+///     .collect();
+/// # }
+/// ```
+pub trait RegexLike {
+    /// Returns whether `haystack`, a file name or a full path, matches.
+    fn is_match_os_str(&self, haystack: &OsStr) -> bool;
+}
+
+impl RegexLike for regex::Regex {
+    fn is_match_os_str(&self, haystack: &OsStr) -> bool {
+        match haystack.to_str() {
+            Some(haystack) => self.is_match(haystack),
+            None => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    "regex unable to evaluate filename as it's not valid unicode. Lossy filename conversion: {}",
+                    haystack.to_string_lossy()
+                );
+                false
+            }
+        }
+    }
+}
+
+impl RegexLike for regex::bytes::Regex {
+    fn is_match_os_str(&self, haystack: &OsStr) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            self.is_match(haystack.as_bytes())
+        }
+        #[cfg(not(unix))]
+        {
+            self.is_match(haystack.to_string_lossy().as_bytes())
+        }
+    }
+}
+
+impl<T: RegexLike + ?Sized> RegexLike for &T {
+    fn is_match_os_str(&self, haystack: &OsStr) -> bool {
+        (**self).is_match_os_str(haystack)
+    }
+}
+
+impl<T: RegexLike + ?Sized> RegexLike for Box<T> {
+    fn is_match_os_str(&self, haystack: &OsStr) -> bool {
+        (**self).is_match_os_str(haystack)
+    }
+}
@@ -0,0 +1,130 @@
+use crate::finder::{path_extensions, search_dirs, Finder, SearchDir, WhichFindIterator};
+use crate::sys::Sys;
+use crate::{
+    CandidateFilter, Checker, NonFatalError, NonFatalErrorHandler, PathExtensions, Result,
+};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// A [`WhichConfig`](crate::WhichConfig) prepared for answering many queries.
+///
+/// The path list is split, tilde expanded and edited once, and the `PATHEXT` extensions and the
+/// cwd are looked up once, when the resolver is built with
+/// [`WhichConfig::resolver`](crate::WhichConfig::resolver). Every query after that only probes
+/// the candidates. Changes to the environment made later aren't noticed; build a new resolver
+/// to pick them up.
+///
+/// Queries take `&self`, and a resolver is `Send` and `Sync` as long as its [`Sys`], checker,
+/// filter and error handler are, so it can be shared between threads. The filter and the error
+/// handler are called behind a lock. Each query checks its candidates one after another, as
+/// [`WhichConfig::parallel`](crate::WhichConfig::parallel) doesn't apply to resolvers.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "real-sys")]
+/// # {
+/// use which::WhichConfig;
+///
+/// let resolver = WhichConfig::new().resolver();
+/// std::thread::scope(|s| {
+///     for name in ["cargo", "rustc", "rustup"] {
+///         let resolver = &resolver;
+///         s.spawn(move || resolver.resolve(name));
+///     }
+/// });
+/// # }
+/// ```
+pub struct Resolver<TSys: Sys, F, C, K> {
+    sys: TSys,
+    /// The directories of the path list, or `None` if there was nowhere to search.
+    dirs: Option<Vec<SearchDir>>,
+    /// The override for the extensions, which decides whether an empty list is worth a
+    /// [`NonFatalError::PathExtNotPopulated`].
+    path_extensions_override: Option<PathExtensions>,
+    path_extensions: Cow<'static, [String]>,
+    cwd: Option<PathBuf>,
+    refuse_relative_path_entries: bool,
+    nonfatal_error_handler: Mutex<F>,
+    filter: Mutex<C>,
+    checker: K,
+}
+
+impl<TSys: Sys, F: NonFatalErrorHandler, C: CandidateFilter, K: Checker> Resolver<TSys, F, C, K> {
+    pub(crate) fn new(
+        finder: Finder<TSys, C, K>,
+        paths: Option<&OsStr>,
+        cwd: Option<PathBuf>,
+        nonfatal_error_handler: F,
+    ) -> Self {
+        let dirs = search_dirs(&finder.sys, paths, &finder.path_list_edits);
+        let path_extensions = path_extensions(&finder.sys, finder.path_extensions.as_ref());
+        Resolver {
+            dirs,
+            path_extensions,
+            path_extensions_override: finder.path_extensions,
+            cwd,
+            refuse_relative_path_entries: finder.refuse_relative_path_entries,
+            nonfatal_error_handler: Mutex::new(nonfatal_error_handler),
+            filter: Mutex::new(finder.filter),
+            checker: finder.checker,
+            sys: finder.sys,
+        }
+    }
+
+    /// Returns the first result for `binary_name`, like
+    /// [`WhichConfig::first_result`](crate::WhichConfig::first_result).
+    pub fn resolve<T: AsRef<OsStr>>(&self, binary_name: T) -> Result<PathBuf> {
+        self.find_iter(binary_name.as_ref())?.first()
+    }
+
+    /// Returns every result for `binary_name`, like
+    /// [`WhichConfig::all_results`](crate::WhichConfig::all_results).
+    pub fn resolve_all<T: AsRef<OsStr>>(
+        &self,
+        binary_name: T,
+    ) -> Result<impl Iterator<Item = PathBuf> + '_> {
+        self.find_iter(binary_name.as_ref())
+    }
+
+    fn find_iter(
+        &self,
+        binary_name: &OsStr,
+    ) -> Result<WhichFindIterator<&TSys, Locked<'_, F>, Locked<'_, C>, &K>> {
+        Finder::new(&self.sys)
+            .refuse_relative_path_entries(self.refuse_relative_path_entries)
+            .path_extensions(self.path_extensions_override.clone())
+            .filter(Locked(&self.filter))
+            .checker(&self.checker)
+            .find_in(
+                PathBuf::from(binary_name),
+                self.dirs.clone(),
+                self.path_extensions.clone(),
+                self.cwd.as_ref(),
+                Locked(&self.nonfatal_error_handler),
+            )
+    }
+}
+
+/// Calls the filter or error handler of a [`Resolver`] or a
+/// [`CachingResolver`](crate::CachingResolver) behind its lock.
+pub(crate) struct Locked<'a, T>(pub(crate) &'a Mutex<T>);
+
+impl<T: CandidateFilter> CandidateFilter for Locked<'_, T> {
+    fn accept(&mut self, candidate: &Path) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .accept(candidate)
+    }
+}
+
+impl<T: NonFatalErrorHandler> NonFatalErrorHandler for Locked<'_, T> {
+    fn handle(&mut self, e: NonFatalError) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle(e)
+    }
+}
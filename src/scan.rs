@@ -0,0 +1,161 @@
+use crate::checker::{is_valid, Checker};
+use crate::finder::{path_extensions, search_dirs, Finder, PathListEdits};
+use crate::helper::has_executable_extension;
+use crate::sys::{Sys, SysReadDirEntry};
+use crate::{CandidateFilter, Error, NonFatalError, NonFatalErrorHandler, Result};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// An executable found while scanning the directories of a path list.
+pub(crate) struct Command {
+    /// Index of the directory in the list returned by [`path_dirs`].
+    pub dir_index: usize,
+    /// Position in the search order within a single directory, see [`command_name`].
+    pub rank: usize,
+    /// The name the executable can be run by.
+    pub name: OsString,
+    /// The name used to compare commands, lowercased on Windows.
+    pub key: OsString,
+    pub path: PathBuf,
+}
+
+/// Splits the path list into the directories to scan, in search order and without duplicates.
+pub(crate) fn path_dirs<TSys: Sys>(
+    sys: TSys,
+    paths: Option<OsString>,
+    edits: &PathListEdits,
+) -> Result<Vec<PathBuf>> {
+    let search_dirs = search_dirs(&sys, paths.as_deref(), edits)
+        .ok_or_else(|| Error::CannotGetCurrentDirAndPathListEmpty(Default::default()))?;
+
+    let mut dirs = Vec::new();
+    for d in search_dirs {
+        if !dirs.contains(&d.dir) {
+            dirs.push(d.dir);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Lists the executables in `dirs`. `wanted` is called with the name of each entry, without its
+/// `PATHEXT` extension on Windows, and its path before its metadata is read, so entries that
+/// aren't of interest can be skipped cheaply.
+pub(crate) fn scan_commands<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>(
+    finder: &mut Finder<TSys, P, K>,
+    dirs: &[PathBuf],
+    nonfatal_error_handler: &mut F,
+    mut wanted: impl FnMut(&OsStr, &Path) -> bool,
+) -> Vec<Command> {
+    let path_extensions = path_extensions(&finder.sys, finder.path_extensions.as_ref());
+    scan_entries(
+        finder,
+        dirs,
+        nonfatal_error_handler,
+        |sys, file_name, path| {
+            command_name(sys, file_name, &path_extensions).filter(|(name, _)| wanted(name, path))
+        },
+    )
+}
+
+/// Like [`scan_commands`], but `select` is called with the file name and path of every entry,
+/// and returns the name and rank to list it under, or `None` to skip it.
+pub(crate) fn scan_entries<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>(
+    finder: &mut Finder<TSys, P, K>,
+    dirs: &[PathBuf],
+    nonfatal_error_handler: &mut F,
+    mut select: impl FnMut(&TSys, &OsStr, &Path) -> Option<(OsString, usize)>,
+) -> Vec<Command> {
+    let sys = &finder.sys;
+
+    let mut commands = Vec::new();
+    for (dir_index, dir) in dirs.iter().enumerate() {
+        let entries = match sys.read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                nonfatal_error_handler.handle(NonFatalError::Io(e));
+                continue;
+            }
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    nonfatal_error_handler.handle(NonFatalError::Io(e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let Some((name, rank)) = select(sys, &entry.file_name(), &path) else {
+                continue;
+            };
+            let key = name_key(sys.is_windows(), &name);
+            if !is_valid(&finder.checker, sys, &path, &mut *nonfatal_error_handler) {
+                continue;
+            }
+            if !finder.filter.accept(&path) {
+                nonfatal_error_handler.handle(NonFatalError::RejectedByFilter(path));
+                continue;
+            }
+            if finder.refuse_relative_path_entries && dir.is_relative() {
+                nonfatal_error_handler.handle(NonFatalError::FoundRelativeToCwd(path));
+                continue;
+            }
+            commands.push(Command {
+                dir_index,
+                rank,
+                name,
+                key,
+                path,
+            });
+        }
+    }
+    commands
+}
+
+/// Returns the name a directory entry can be run by, along with its position in the search
+/// order within its directory. On Windows a file without an extension is tried first, followed
+/// by each extension in `PATHEXT` in order.
+pub(crate) fn command_name<TSys: Sys>(
+    sys: TSys,
+    file_name: &OsStr,
+    path_extensions: &[String],
+) -> Option<(OsString, usize)> {
+    let path = Path::new(file_name);
+    if !sys.is_windows() || path.extension().is_none() {
+        return Some((file_name.to_os_string(), 0));
+    }
+    let extension = path.extension()?.to_str()?;
+    let rank = path_extensions.iter().position(|e| {
+        e.strip_prefix('.')
+            .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+    })?;
+    Some((path.file_stem()?.to_os_string(), rank + 1))
+}
+
+/// Returns the key of each name a single lookup finds `file_name` by, along with its position in
+/// the search order within its directory. Any name finds the file with that name first, and on
+/// Windows a name without a `PATHEXT` extension, like `python`, then finds it with each
+/// extension appended in turn.
+pub(crate) fn lookup_keys<TSys: Sys>(
+    sys: TSys,
+    file_name: &OsStr,
+    path_extensions: &[String],
+) -> Vec<(OsString, usize)> {
+    let is_windows = sys.is_windows();
+    let mut keys = vec![(name_key(is_windows, file_name), 0)];
+    if let Some((name, rank)) = command_name(sys, file_name, path_extensions) {
+        if rank > 0 && !has_executable_extension(&name, path_extensions) {
+            keys.push((name_key(is_windows, &name), rank));
+        }
+    }
+    keys
+}
+
+/// The name used to compare commands, lowercased on Windows.
+pub(crate) fn name_key(is_windows: bool, name: &OsStr) -> OsString {
+    if is_windows {
+        name.to_string_lossy().to_lowercase().into()
+    } else {
+        name.to_os_string()
+    }
+}
@@ -0,0 +1,54 @@
+use crate::finder::Finder;
+use crate::scan::{path_dirs, scan_commands, Command};
+use crate::sys::Sys;
+use crate::{CandidateFilter, Checker, NonFatalErrorHandler, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// An executable together with every later match it hides from the search.
+///
+/// Produced by [`WhichConfig::shadowing`](crate::WhichConfig::shadowing) and
+/// [`WhichConfig::all_shadowed`](crate::WhichConfig::all_shadowed).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Shadowing {
+    /// The command name that was resolved.
+    pub name: OsString,
+    /// The path `which` returns for `name`.
+    pub active: PathBuf,
+    /// Every later match for `name`, in search order.
+    pub shadowed: Vec<PathBuf>,
+}
+
+pub fn all_shadowed<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>(
+    mut finder: Finder<TSys, P, K>,
+    paths: Option<OsString>,
+    mut nonfatal_error_handler: F,
+) -> Result<Vec<Shadowing>> {
+    // A directory listed twice in PATH doesn't shadow itself, so duplicates are dropped here.
+    let dirs = path_dirs(&finder.sys, paths, &finder.path_list_edits)?;
+
+    let mut by_name = BTreeMap::<OsString, Vec<Command>>::new();
+    for command in scan_commands(&mut finder, &dirs, &mut nonfatal_error_handler, |_, _| true) {
+        by_name
+            .entry(command.key.clone())
+            .or_default()
+            .push(command);
+    }
+
+    Ok(by_name
+        .into_values()
+        .filter(|found| found.iter().any(|f| f.dir_index != found[0].dir_index))
+        .map(|mut found| {
+            found.sort_by_key(|f| (f.dir_index, f.rank));
+            let mut found = found.into_iter();
+            let first = found.next().expect("groups are never empty");
+            Shadowing {
+                name: first.name,
+                active: first.path,
+                shadowed: found.map(|f| f.path).collect(),
+            }
+        })
+        .collect())
+}
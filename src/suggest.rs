@@ -0,0 +1,72 @@
+use crate::finder::Finder;
+use crate::helper::edit_distance;
+use crate::scan::{path_dirs, scan_commands};
+use crate::sys::Sys;
+use crate::{CandidateFilter, Checker, NonFatalErrorHandler, Result};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// An executable on the path list with a name similar to the one searched for.
+///
+/// Produced by [`WhichConfig::suggestions`](crate::WhichConfig::suggestions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Suggestion {
+    /// The command name, without its `PATHEXT` extension on Windows.
+    pub name: OsString,
+    /// The path `which` returns for `name`.
+    pub path: PathBuf,
+    /// The edit distance between `name` and the name searched for.
+    pub distance: usize,
+}
+
+pub fn suggestions<TSys: Sys, F: NonFatalErrorHandler, P: CandidateFilter, K: Checker>(
+    mut finder: Finder<TSys, P, K>,
+    binary_name: &OsStr,
+    paths: Option<OsString>,
+    mut nonfatal_error_handler: F,
+) -> Result<Vec<Suggestion>> {
+    let dirs = path_dirs(&finder.sys, paths, &finder.path_list_edits)?;
+    let is_windows = finder.sys.is_windows();
+    let query = if is_windows {
+        binary_name.to_string_lossy().to_lowercase()
+    } else {
+        binary_name.to_string_lossy().into_owned()
+    };
+    let max_distance = (query.chars().count() / 3).max(1);
+    let distance = |key: &OsStr| edit_distance(&query, &key.to_string_lossy());
+
+    let mut commands = scan_commands(
+        &mut finder,
+        &dirs,
+        &mut nonfatal_error_handler,
+        |name, _| {
+            let name = name.to_string_lossy();
+            let key = if is_windows {
+                name.to_lowercase()
+            } else {
+                name.into_owned()
+            };
+            edit_distance(&query, &key) <= max_distance
+        },
+    );
+    // Only the first match for each name is what would actually run.
+    commands.sort_by(|a, b| (&a.key, a.dir_index, a.rank).cmp(&(&b.key, b.dir_index, b.rank)));
+    commands.dedup_by(|a, b| a.key == b.key);
+
+    let mut suggestions = commands
+        .into_iter()
+        .map(|c| (c.dir_index, distance(&c.key), c))
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|(a_dir, a_distance, a), (b_dir, b_distance, b)| {
+        (a_distance, a_dir, &a.key).cmp(&(b_distance, b_dir, &b.key))
+    });
+    Ok(suggestions
+        .into_iter()
+        .map(|(_, distance, c)| Suggestion {
+            name: c.name,
+            path: c.path,
+            distance,
+        })
+        .collect())
+}
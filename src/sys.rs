@@ -4,6 +4,12 @@ use std::ffi::OsString;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::SystemTime;
+
+mod env_override;
+pub mod memory;
+
+pub use env_override::EnvOverride;
 
 pub trait SysReadDirEntry {
     /// Gets the file name of the directory entry, not the full path.
@@ -17,6 +23,31 @@ pub trait SysMetadata {
     fn is_symlink(&self) -> bool;
     /// Gets if the path is a file.
     fn is_file(&self) -> bool;
+    /// Gets if the path is a directory.
+    ///
+    /// Defaults to anything that is neither a file nor a symlink.
+    fn is_dir(&self) -> bool {
+        !self.is_file() && !self.is_symlink()
+    }
+    /// Gets the Unix permission bits of the path, if the platform has them.
+    fn unix_mode(&self) -> Option<u32> {
+        None
+    }
+    /// Gets the last modification time of the path.
+    ///
+    /// Defaults to an [`io::ErrorKind::Unsupported`] error, in which case a
+    /// [`CachingResolver`](crate::CachingResolver) can only expire its entries by age.
+    fn modified(&self) -> io::Result<SystemTime> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "modification times aren't supported",
+        ))
+    }
+    /// Gets the inode number of the path, if the platform has them. Used with
+    /// [`Self::modified`] to notice directories that were replaced.
+    fn inode(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// Represents the system that `which` interacts with to get information
@@ -66,6 +97,27 @@ pub trait Sys {
     fn env_path(&self) -> Option<OsString>;
     /// Gets the value of the PATHEXT environment variable. If not on Windows, simply return None.
     fn env_path_ext(&self) -> Option<OsString>;
+    /// Gets the value of an environment variable, for
+    /// [`WhichConfig::path_list_var`](crate::WhichConfig::path_list_var).
+    ///
+    /// Defaults to [`Self::env_path`] for `PATH`, [`Self::env_path_ext`] for `PATHEXT` and
+    /// `None` for every other variable.
+    fn env_var_os(&self, name: &OsStr) -> Option<OsString> {
+        let is = |var: &str| {
+            if self.is_windows() {
+                name.to_string_lossy().eq_ignore_ascii_case(var)
+            } else {
+                name == var
+            }
+        };
+        if is("PATH") {
+            self.env_path()
+        } else if is("PATHEXT") {
+            self.env_path_ext()
+        } else {
+            None
+        }
+    }
     /// Gets and parses the PATHEXT environment variable on Windows.
     ///
     /// Override this to enable caching the parsed PATHEXT.
@@ -87,6 +139,39 @@ pub trait Sys {
     ) -> io::Result<Box<dyn Iterator<Item = io::Result<Self::ReadDirEntry>>>>;
     /// Checks if the provided path is a valid executable.
     fn is_valid_executable(&self, path: &Path) -> io::Result<bool>;
+    /// Returns the canonical, absolute form of the path with all symlinks resolved.
+    ///
+    /// Defaults to returning an [`io::ErrorKind::Unsupported`] error.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let _ = path;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "canonicalize is not supported",
+        ))
+    }
+    /// Reads the entire contents of a file, following symlinks. Used by
+    /// [`LibraryConfig`](crate::LibraryConfig) to read ELF objects and the dynamic loader's
+    /// configuration.
+    ///
+    /// Defaults to returning an [`io::ErrorKind::Unsupported`] error.
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let _ = path;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "read_file is not supported",
+        ))
+    }
+    /// Replaces the contents of a file, creating it and its parent directories if needed. Used
+    /// by [`CommandIndex`](crate::CommandIndex) to save the index.
+    ///
+    /// Defaults to returning an [`io::ErrorKind::Unsupported`] error.
+    fn replace_file(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let _ = (path, contents);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "replace_file is not supported",
+        ))
+    }
 }
 
 impl SysReadDirEntry for std::fs::DirEntry {
@@ -107,18 +192,58 @@ impl SysMetadata for std::fs::Metadata {
     fn is_file(&self) -> bool {
         self.file_type().is_file()
     }
+
+    fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    #[cfg(unix)]
+    fn unix_mode(&self) -> Option<u32> {
+        use std::os::unix::fs::PermissionsExt;
+        Some(self.permissions().mode())
+    }
+
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified()
+    }
+
+    #[cfg(unix)]
+    fn inode(&self) -> Option<u64> {
+        use std::os::unix::fs::MetadataExt;
+        Some(self.ino())
+    }
 }
 
 #[cfg(feature = "real-sys")]
 #[derive(Default, Clone, Copy)]
 pub struct RealSys;
 
+// Sample %PATHEXT%: .COM;.EXE;.BAT;.CMD;.VBS;.VBE;.JS;.JSE;.WSF;.WSH;.MSC
+// PATH_EXTENSIONS is then [".COM", ".EXE", ".BAT", …].
+// (In one use of PATH_EXTENSIONS we skip the dot, but in the other we need it;
+// hence its retention.)
+#[cfg(feature = "real-sys")]
+static PATH_EXTENSIONS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+
+/// Set by [`RealSys::refresh_path_ext`], and preferred over `PATH_EXTENSIONS` once set. It can't
+/// be handed out with a `'static` lifetime, so it's cloned on every lookup instead.
+#[cfg(feature = "real-sys")]
+static REFRESHED_PATH_EXTENSIONS: std::sync::RwLock<Option<Vec<String>>> =
+    std::sync::RwLock::new(None);
+
 #[cfg(feature = "real-sys")]
 impl RealSys {
-    #[inline]
-    pub(crate) fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
-        #[allow(clippy::disallowed_methods)] // ok, sys implementation
-        std::fs::canonicalize(path)
+    /// Re-reads the `PATHEXT` environment variable.
+    ///
+    /// `RealSys` reads `PATHEXT` once and caches it for the life of the process, so changes made
+    /// to it afterwards aren't seen until this is called. The cache is shared by every
+    /// `RealSys`. To use different extensions for a single query, see
+    /// [`WhichConfig::path_extensions`](crate::WhichConfig::path_extensions).
+    pub fn refresh_path_ext(&self) {
+        let path_extensions = parse_path_ext(self.env_path_ext());
+        *REFRESHED_PATH_EXTENSIONS
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(path_extensions);
     }
 }
 
@@ -155,13 +280,13 @@ impl Sys for RealSys {
     }
 
     fn env_windows_path_ext(&self) -> Cow<'static, [String]> {
-        use std::sync::OnceLock;
-
-        // Sample %PATHEXT%: .COM;.EXE;.BAT;.CMD;.VBS;.VBE;.JS;.JSE;.WSF;.WSH;.MSC
-        // PATH_EXTENSIONS is then [".COM", ".EXE", ".BAT", …].
-        // (In one use of PATH_EXTENSIONS we skip the dot, but in the other we need it;
-        // hence its retention.)
-        static PATH_EXTENSIONS: OnceLock<Vec<String>> = OnceLock::new();
+        if let Some(path_extensions) = REFRESHED_PATH_EXTENSIONS
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            return Cow::Owned(path_extensions.clone());
+        }
         let path_extensions = PATH_EXTENSIONS.get_or_init(|| parse_path_ext(self.env_path_ext()));
         Cow::Borrowed(path_extensions)
     }
@@ -178,6 +303,12 @@ impl Sys for RealSys {
         std::env::var_os("PATHEXT")
     }
 
+    #[inline]
+    fn env_var_os(&self, name: &OsStr) -> Option<OsString> {
+        #[allow(clippy::disallowed_methods)] // ok, sys implementation
+        std::env::var_os(name)
+    }
+
     #[inline]
     fn read_dir(
         &self,
@@ -241,6 +372,45 @@ impl Sys for RealSys {
     fn is_valid_executable(&self, _path: &Path) -> io::Result<bool> {
         Ok(false)
     }
+
+    #[inline]
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        #[allow(clippy::disallowed_methods)] // ok, sys implementation
+        std::fs::canonicalize(path)
+    }
+
+    #[inline]
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        #[allow(clippy::disallowed_methods)] // ok, sys implementation
+        std::fs::read(path)
+    }
+
+    /// Writes to a temporary file next to `path` first and renames it over `path`, so readers
+    /// never see a partially written file. Every call uses its own temporary file, so threads
+    /// and processes replacing the same file don't interfere.
+    fn replace_file(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+        if let Some(parent) = path.parent() {
+            #[allow(clippy::disallowed_methods)] // ok, sys implementation
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut temp = path.as_os_str().to_os_string();
+        temp.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        #[allow(clippy::disallowed_methods)] // ok, sys implementation
+        let result = std::fs::write(&temp, contents).and_then(|()| std::fs::rename(&temp, path));
+        if result.is_err() {
+            #[allow(clippy::disallowed_methods)] // ok, sys implementation
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
 }
 
 impl<T> Sys for &T
@@ -275,6 +445,14 @@ where
         (*self).env_path_ext()
     }
 
+    fn env_var_os(&self, name: &OsStr) -> Option<OsString> {
+        (*self).env_var_os(name)
+    }
+
+    fn env_windows_path_ext(&self) -> Cow<'static, [String]> {
+        (*self).env_windows_path_ext()
+    }
+
     fn metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
         (*self).metadata(path)
     }
@@ -293,6 +471,18 @@ where
     fn is_valid_executable(&self, path: &Path) -> io::Result<bool> {
         (*self).is_valid_executable(path)
     }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        (*self).canonicalize(path)
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (*self).read_file(path)
+    }
+
+    fn replace_file(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (*self).replace_file(path, contents)
+    }
 }
 
 fn parse_path_ext(pathext: Option<OsString>) -> Vec<String> {
@@ -0,0 +1,234 @@
+use super::Sys;
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A [`Sys`] that sees a modified copy of another [`Sys`]'s environment, to resolve programs the
+/// way a child process started with a different environment would, without touching the
+/// environment of the current process.
+///
+/// The methods mirror those of [`std::process::Command`]. Environment variables, the home
+/// directory and the current directory are taken from the overrides, everything else is passed
+/// through to the wrapped [`Sys`], with relative paths resolved against the overridden current
+/// directory.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "real-sys")]
+/// # {
+/// use which::sys::{EnvOverride, RealSys};
+/// use which::WhichConfig;
+///
+/// let sys = EnvOverride::new(RealSys)
+///     .env("PATH", "/opt/toolchain/bin")
+///     .current_dir("/tmp");
+/// let cc = WhichConfig::new_with_sys(sys)
+///     .binary_name("cc".into())
+///     .first_result();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvOverride<TSys> {
+    inner: TSys,
+    /// Overridden variables in the order they were set. `None` means removed.
+    vars: Vec<(OsString, Option<OsString>)>,
+    clear: bool,
+    cwd: Option<PathBuf>,
+}
+
+impl<TSys: Sys> EnvOverride<TSys> {
+    /// Wraps `inner` without overriding anything.
+    pub fn new(inner: TSys) -> Self {
+        Self {
+            inner,
+            vars: Vec::new(),
+            clear: false,
+            cwd: None,
+        }
+    }
+
+    /// Wraps `inner`, overriding the given environment variables. Equivalent to
+    /// `EnvOverride::new(inner).envs(vars)`.
+    pub fn from_envs<I, K, V>(inner: TSys, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        Self::new(inner).envs(vars)
+    }
+
+    /// Sets an environment variable.
+    pub fn env(mut self, key: impl AsRef<OsStr>, val: impl AsRef<OsStr>) -> Self {
+        self.set(key.as_ref(), Some(val.as_ref().to_os_string()));
+        self
+    }
+
+    /// Sets several environment variables.
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (key, val) in vars {
+            self.set(key.as_ref(), Some(val.as_ref().to_os_string()));
+        }
+        self
+    }
+
+    /// Removes an environment variable.
+    pub fn env_remove(mut self, key: impl AsRef<OsStr>) -> Self {
+        self.set(key.as_ref(), None);
+        self
+    }
+
+    /// Removes every environment variable, including those set earlier. Variables set afterwards
+    /// are kept.
+    pub fn env_clear(mut self) -> Self {
+        self.vars.clear();
+        self.clear = true;
+        self
+    }
+
+    /// Sets the current directory. A relative path is resolved against the current directory of
+    /// the wrapped [`Sys`].
+    pub fn current_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.cwd = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the wrapped [`Sys`].
+    pub fn into_inner(self) -> TSys {
+        self.inner
+    }
+
+    /// Resolves relative paths against the overridden current directory, since the wrapped
+    /// [`Sys`] would resolve them against its own.
+    fn absolute<'a>(&self, path: &'a Path) -> io::Result<Cow<'a, Path>> {
+        if self.cwd.is_some() && path.is_relative() {
+            Ok(Cow::Owned(self.current_dir()?.join(path)))
+        } else {
+            Ok(Cow::Borrowed(path))
+        }
+    }
+
+    fn set(&mut self, key: &OsStr, val: Option<OsString>) {
+        let is_windows = self.inner.is_windows();
+        self.vars.retain(|(k, _)| !same_key(is_windows, k, key));
+        self.vars.push((key.to_os_string(), val));
+    }
+
+    /// Returns `Some` with the overridden value if `key` is overridden, `None` if it's inherited.
+    fn get(&self, key: impl AsRef<OsStr>) -> Option<Option<OsString>> {
+        let is_windows = self.inner.is_windows();
+        match self
+            .vars
+            .iter()
+            .find(|(k, _)| same_key(is_windows, k, key.as_ref()))
+        {
+            Some((_, val)) => Some(val.clone()),
+            None if self.clear => Some(None),
+            None => None,
+        }
+    }
+}
+
+/// Environment variable names are case insensitive on Windows.
+fn same_key(is_windows: bool, a: &OsStr, b: &OsStr) -> bool {
+    if is_windows {
+        a.to_string_lossy()
+            .eq_ignore_ascii_case(&b.to_string_lossy())
+    } else {
+        a == b
+    }
+}
+
+impl<TSys: Sys> Sys for EnvOverride<TSys> {
+    type ReadDirEntry = TSys::ReadDirEntry;
+    type Metadata = TSys::Metadata;
+
+    fn is_windows(&self) -> bool {
+        self.inner.is_windows()
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        match &self.cwd {
+            Some(cwd) if cwd.is_absolute() => Ok(cwd.clone()),
+            Some(cwd) => Ok(self.inner.current_dir()?.join(cwd)),
+            None => self.inner.current_dir(),
+        }
+    }
+
+    /// Returns `HOME`, or `USERPROFILE` on Windows, if it was overridden, and `None` if it was
+    /// removed.
+    fn home_dir(&self) -> Option<PathBuf> {
+        let key = if self.inner.is_windows() {
+            "USERPROFILE"
+        } else {
+            "HOME"
+        };
+        match self.get(key) {
+            Some(home) => home.filter(|h| !h.is_empty()).map(PathBuf::from),
+            None => self.inner.home_dir(),
+        }
+    }
+
+    fn env_split_paths(&self, paths: &OsStr) -> Vec<PathBuf> {
+        self.inner.env_split_paths(paths)
+    }
+
+    fn env_path(&self) -> Option<OsString> {
+        self.get("PATH").unwrap_or_else(|| self.inner.env_path())
+    }
+
+    fn env_path_ext(&self) -> Option<OsString> {
+        self.get("PATHEXT")
+            .unwrap_or_else(|| self.inner.env_path_ext())
+    }
+
+    fn env_var_os(&self, name: &OsStr) -> Option<OsString> {
+        self.get(name)
+            .unwrap_or_else(|| self.inner.env_var_os(name))
+    }
+
+    fn env_windows_path_ext(&self) -> Cow<'static, [String]> {
+        match self.get("PATHEXT") {
+            Some(path_ext) => Cow::Owned(super::parse_path_ext(path_ext)),
+            // Keep any caching the wrapped Sys does.
+            None => self.inner.env_windows_path_ext(),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
+        self.inner.metadata(&self.absolute(path)?)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
+        self.inner.symlink_metadata(&self.absolute(path)?)
+    }
+
+    fn read_dir(
+        &self,
+        path: &Path,
+    ) -> io::Result<Box<dyn Iterator<Item = io::Result<Self::ReadDirEntry>>>> {
+        self.inner.read_dir(&self.absolute(path)?)
+    }
+
+    fn is_valid_executable(&self, path: &Path) -> io::Result<bool> {
+        self.inner.is_valid_executable(&self.absolute(path)?)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.inner.canonicalize(&self.absolute(path)?)
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inner.read_file(&self.absolute(path)?)
+    }
+
+    fn replace_file(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.inner.replace_file(&self.absolute(path)?, contents)
+    }
+}
@@ -0,0 +1,646 @@
+//! An in-memory [`Sys`] for testing code that uses `which` without touching the real system.
+//!
+//! ```
+//! use std::path::PathBuf;
+//! use which::sys::memory::InMemorySys;
+//! use which::WhichConfig;
+//!
+//! let sys = InMemorySys::new()
+//!     .env_var("PATH", "/usr/local/bin:/usr/bin")
+//!     .file("/usr/local/bin/git")
+//!     .executable("/usr/bin/git");
+//! let git = WhichConfig::new_with_sys(sys)
+//!     .binary_name("git".into())
+//!     .first_result()
+//!     .unwrap();
+//! assert_eq!(git, PathBuf::from("/usr/bin/git"));
+//! ```
+//!
+//! The same system can be described with a fixture:
+//!
+//! ```
+//! # use which::sys::memory::InMemorySys;
+//! let sys = InMemorySys::from_fixture(
+//!     r"
+//!     env PATH=/usr/local/bin:/usr/bin
+//!     file /usr/local/bin/git
+//!     executable /usr/bin/git
+//!     ",
+//! )
+//! .unwrap();
+//! ```
+//!
+//! Paths use the syntax of the host, so `/`-rooted paths work everywhere, including when
+//! emulating Windows.
+
+use super::{Sys, SysMetadata, SysReadDirEntry};
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Symlinks followed while resolving a single path before giving up, like Linux's `ELOOP`.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// A file system, environment and platform held in memory.
+///
+/// Defaults to emulating Unix, with `/` as the current directory, no home directory and no
+/// environment variables. Use [`InMemorySys::windows`] to emulate Windows.
+///
+/// Adding an entry creates any missing parent directories. Adding an entry below a file or
+/// symlink panics.
+#[derive(Debug, Clone)]
+pub struct InMemorySys {
+    is_windows: bool,
+    cwd: PathBuf,
+    home_dir: Option<PathBuf>,
+    env_vars: BTreeMap<OsString, OsString>,
+    /// Every entry by normalized absolute path, except for the root directory.
+    entries: BTreeMap<PathBuf, Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Directory,
+    File { executable: bool, contents: Vec<u8> },
+    Symlink { target: PathBuf },
+}
+
+impl Default for InMemorySys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemorySys {
+    /// Creates an empty system that emulates Unix.
+    pub fn new() -> Self {
+        Self {
+            is_windows: false,
+            cwd: PathBuf::from("/"),
+            home_dir: None,
+            env_vars: BTreeMap::new(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an empty system that emulates Windows.
+    ///
+    /// `PATH` is split on `;`, names of files and environment variables are compared case
+    /// insensitively, and `PATHEXT` is set to `.COM;.EXE;.BAT;.CMD`.
+    pub fn windows() -> Self {
+        Self {
+            is_windows: true,
+            ..Self::new()
+        }
+        .env_var("PATHEXT", ".COM;.EXE;.BAT;.CMD")
+    }
+
+    /// Parses a fixture describing a system.
+    ///
+    /// Each line holds one directive. Blank lines and lines starting with `#` are ignored.
+    ///
+    /// | Directive | Effect |
+    /// |---|---|
+    /// | `windows` | Emulate Windows, see [`InMemorySys::windows`]. Must come first. |
+    /// | `cwd <path>` | [`InMemorySys::cwd`] |
+    /// | `home <path>` | [`InMemorySys::home`] |
+    /// | `env <name>=<value>` | [`InMemorySys::env_var`] |
+    /// | `directory <path>` | [`InMemorySys::directory`] |
+    /// | `file <path>` | [`InMemorySys::file`] |
+    /// | `executable <path>` | [`InMemorySys::executable`] |
+    /// | `symlink <path> -> <target>` | [`InMemorySys::symlink`] |
+    pub fn from_fixture(fixture: &str) -> Result<Self, FixtureError> {
+        let mut sys = Self::new();
+        let mut first = true;
+        for (index, line) in fixture.lines().enumerate() {
+            let error = |message: &str| FixtureError {
+                line: index + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let is_first = std::mem::replace(&mut first, false);
+            let (directive, arg) = match line.split_once(char::is_whitespace) {
+                Some((directive, arg)) => (directive, arg.trim()),
+                None => (line, ""),
+            };
+            if directive == "windows" {
+                if !arg.is_empty() {
+                    return Err(error("`windows` takes no argument"));
+                }
+                if !is_first {
+                    return Err(error("`windows` must come before any other directive"));
+                }
+                sys = Self::windows();
+                continue;
+            }
+            if arg.is_empty() {
+                return Err(error(&format!("`{directive}` needs an argument")));
+            }
+            let path = Path::new(arg);
+            let check_parents = |sys: &Self, path: &Path| {
+                if sys.insertion_blocked(path) {
+                    Err(error("a parent is a file or symlink"))
+                } else if normalize(&sys.cwd, path).parent().is_none() {
+                    Err(error("cannot replace the root directory"))
+                } else {
+                    Ok(())
+                }
+            };
+            match directive {
+                "cwd" => sys.set_cwd(path),
+                "home" => sys.set_home(path),
+                "env" => {
+                    let (name, value) = arg
+                        .split_once('=')
+                        .ok_or_else(|| error("expected `env <name>=<value>`"))?;
+                    sys.set_env_var(name, value);
+                }
+                "directory" => {
+                    check_parents(&sys, path)?;
+                    sys.create_directory(path);
+                }
+                "file" => {
+                    check_parents(&sys, path)?;
+                    sys.write_file(path);
+                }
+                "executable" => {
+                    check_parents(&sys, path)?;
+                    sys.write_executable(path);
+                }
+                "symlink" => {
+                    let (link, target) = arg
+                        .split_once(" -> ")
+                        .ok_or_else(|| error("expected `symlink <path> -> <target>`"))?;
+                    let link = Path::new(link.trim());
+                    check_parents(&sys, link)?;
+                    sys.create_symlink(link, target.trim());
+                }
+                _ => return Err(error(&format!("unknown directive `{directive}`"))),
+            }
+        }
+        Ok(sys)
+    }
+
+    /// Sets the current working directory, which relative paths are resolved against.
+    pub fn cwd(mut self, path: impl AsRef<Path>) -> Self {
+        self.set_cwd(path);
+        self
+    }
+
+    /// Sets the home directory of the current user.
+    pub fn home(mut self, path: impl AsRef<Path>) -> Self {
+        self.set_home(path);
+        self
+    }
+
+    /// Sets an environment variable.
+    pub fn env_var(mut self, name: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.set_env_var(name, value);
+        self
+    }
+
+    /// Adds a directory.
+    pub fn directory(mut self, path: impl AsRef<Path>) -> Self {
+        self.create_directory(path);
+        self
+    }
+
+    /// Adds a file that isn't executable.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.write_file(path);
+        self
+    }
+
+    /// Adds an executable file.
+    pub fn executable(mut self, path: impl AsRef<Path>) -> Self {
+        self.write_executable(path);
+        self
+    }
+
+    /// Adds a file that isn't executable with the given contents, for
+    /// [`Sys::read_file`].
+    pub fn file_with_contents(
+        mut self,
+        path: impl AsRef<Path>,
+        contents: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.write_file_with_contents(path, contents);
+        self
+    }
+
+    /// Adds a symlink at `path` pointing to `target`. A relative `target` is resolved against the
+    /// directory containing the symlink.
+    pub fn symlink(mut self, path: impl AsRef<Path>, target: impl AsRef<Path>) -> Self {
+        self.create_symlink(path, target);
+        self
+    }
+
+    /// Sets the current working directory, which relative paths are resolved against.
+    pub fn set_cwd(&mut self, path: impl AsRef<Path>) {
+        self.cwd = normalize(Path::new("/"), path.as_ref());
+    }
+
+    /// Sets the home directory of the current user.
+    pub fn set_home(&mut self, path: impl AsRef<Path>) {
+        self.home_dir = Some(path.as_ref().to_path_buf());
+    }
+
+    /// Sets an environment variable.
+    pub fn set_env_var(&mut self, name: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let name = self.env_var_key(name.as_ref()).unwrap_or(name.as_ref());
+        self.env_vars
+            .insert(name.to_os_string(), value.as_ref().to_os_string());
+    }
+
+    /// Removes an environment variable.
+    pub fn remove_env_var(&mut self, name: impl AsRef<OsStr>) {
+        if let Some(name) = self.env_var_key(name.as_ref()).map(OsStr::to_os_string) {
+            self.env_vars.remove(&name);
+        }
+    }
+
+    /// Adds a directory.
+    pub fn create_directory(&mut self, path: impl AsRef<Path>) {
+        self.insert(path.as_ref(), Node::Directory);
+    }
+
+    /// Adds a file that isn't executable.
+    pub fn write_file(&mut self, path: impl AsRef<Path>) {
+        self.write_file_with_contents(path, Vec::new());
+    }
+
+    /// Adds an executable file.
+    pub fn write_executable(&mut self, path: impl AsRef<Path>) {
+        self.insert(
+            path.as_ref(),
+            Node::File {
+                executable: true,
+                contents: Vec::new(),
+            },
+        );
+    }
+
+    /// Adds a file that isn't executable with the given contents, for
+    /// [`Sys::read_file`].
+    pub fn write_file_with_contents(
+        &mut self,
+        path: impl AsRef<Path>,
+        contents: impl Into<Vec<u8>>,
+    ) {
+        self.insert(
+            path.as_ref(),
+            Node::File {
+                executable: false,
+                contents: contents.into(),
+            },
+        );
+    }
+
+    /// Adds a symlink at `path` pointing to `target`. A relative `target` is resolved against the
+    /// directory containing the symlink.
+    pub fn create_symlink(&mut self, path: impl AsRef<Path>, target: impl AsRef<Path>) {
+        self.insert(
+            path.as_ref(),
+            Node::Symlink {
+                target: target.as_ref().to_path_buf(),
+            },
+        );
+    }
+
+    /// Removes an entry, and everything below it if it's a directory. Symlinks aren't followed.
+    pub fn remove(&mut self, path: impl AsRef<Path>) {
+        let path = normalize(&self.cwd, path.as_ref());
+        let Some(key) = self.key(&path).cloned() else {
+            return;
+        };
+        self.entries.retain(|p, _| !p.starts_with(&key));
+    }
+
+    fn insert(&mut self, path: &Path, node: Node) {
+        let path = normalize(&self.cwd, path);
+        assert!(
+            path.parent().is_some(),
+            "cannot replace the root of an in-memory file system"
+        );
+        assert!(
+            !self.insertion_blocked(&path),
+            "cannot add {} to an in-memory file system, a parent is a file or symlink",
+            path.display()
+        );
+        // Reuse the spelling of existing parents, which differs in case when emulating Windows.
+        let mut key = PathBuf::from("/");
+        for name in path.iter().skip(1) {
+            key = match self.key(&key.join(name)) {
+                Some(existing) => existing.clone(),
+                None => {
+                    let new = key.join(name);
+                    self.entries.insert(new.clone(), Node::Directory);
+                    new
+                }
+            };
+        }
+        if matches!(node, Node::Directory) {
+            if let Some(existing) = self.entries.get_mut(&key) {
+                if !matches!(existing, Node::Directory) {
+                    *existing = Node::Directory;
+                }
+                return;
+            }
+        }
+        // Replacing a directory drops its contents.
+        self.entries
+            .retain(|p, _| !p.starts_with(&key) || *p == key);
+        self.entries.insert(key, node);
+    }
+
+    /// Whether a parent of `path` is something other than a directory.
+    fn insertion_blocked(&self, path: &Path) -> bool {
+        let path = normalize(&self.cwd, path);
+        path.ancestors().skip(1).any(|ancestor| {
+            self.key(ancestor)
+                .is_some_and(|key| !matches!(self.entries[key], Node::Directory))
+        })
+    }
+
+    /// Finds the key an absolute, normalized path is stored under, ignoring case on Windows.
+    fn key(&self, path: &Path) -> Option<&PathBuf> {
+        if let Some((key, _)) = self.entries.get_key_value(path) {
+            return Some(key);
+        }
+        if !self.is_windows {
+            return None;
+        }
+        let path = path.to_string_lossy().to_lowercase();
+        self.entries
+            .keys()
+            .find(|key| key.to_string_lossy().to_lowercase() == path)
+    }
+
+    fn env_var_key(&self, name: &OsStr) -> Option<&OsStr> {
+        if let Some((key, _)) = self.env_vars.get_key_value(name) {
+            return Some(key);
+        }
+        if !self.is_windows {
+            return None;
+        }
+        let name = name.to_string_lossy().to_lowercase();
+        self.env_vars
+            .keys()
+            .find(|key| key.to_string_lossy().to_lowercase() == name)
+            .map(OsString::as_os_str)
+    }
+
+    /// Resolves `path` to the key of the entry it refers to, following symlinks in every
+    /// component and, if `follow` is set, in the last one too. The root directory resolves to
+    /// `/`, which has no entry.
+    fn resolve(&self, path: &Path, follow: bool) -> io::Result<PathBuf> {
+        let mut pending = Vec::new();
+        push_components(&mut pending, &self.cwd.join(path));
+        let mut current = PathBuf::from("/");
+        let mut hops = 0;
+        while let Some(part) = pending.pop() {
+            let name = match part {
+                Part::Root => {
+                    current = PathBuf::from("/");
+                    continue;
+                }
+                Part::Parent => {
+                    current.pop();
+                    continue;
+                }
+                Part::Name(name) => name,
+            };
+            let key = self
+                .key(&current.join(&name))
+                .ok_or_else(|| not_found(path))?;
+            match &self.entries[key] {
+                Node::Symlink { target } if follow || !pending.is_empty() => {
+                    hops += 1;
+                    if hops > MAX_SYMLINK_HOPS {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            format!("too many levels of symbolic links: {}", path.display()),
+                        ));
+                    }
+                    push_components(&mut pending, target);
+                }
+                Node::File { .. } | Node::Symlink { .. } if !pending.is_empty() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("not a directory: {}", path.display()),
+                    ));
+                }
+                _ => current = key.clone(),
+            }
+        }
+        Ok(current)
+    }
+
+    fn node(&self, path: &Path, follow: bool) -> io::Result<&Node> {
+        let key = self.resolve(path, follow)?;
+        Ok(self.entries.get(&key).unwrap_or(&Node::Directory))
+    }
+}
+
+/// A line of a fixture passed to [`InMemorySys::from_fixture`] that couldn't be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FixtureError {
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// What is wrong with the line.
+    pub message: String,
+}
+
+impl std::error::Error for FixtureError {}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fixture line {}: {}", self.line, self.message)
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no such file or directory: {}", path.display()),
+    )
+}
+
+enum Part {
+    Root,
+    Parent,
+    Name(OsString),
+}
+
+/// Pushes the components of `path` onto a stack, so that they pop off in order.
+fn push_components(stack: &mut Vec<Part>, path: &Path) {
+    let start = stack.len();
+    for component in path.components() {
+        match component {
+            // Drive letters have no meaning in memory.
+            Component::Prefix(_) | Component::CurDir => {}
+            Component::RootDir => stack.push(Part::Root),
+            Component::ParentDir => stack.push(Part::Parent),
+            Component::Normal(name) => stack.push(Part::Name(name.to_os_string())),
+        }
+    }
+    stack[start..].reverse();
+}
+
+/// Makes `path` absolute and removes `.` and `..` components, without following symlinks.
+fn normalize(cwd: &Path, path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in cwd.join(path).components() {
+        match component {
+            Component::Prefix(_) | Component::CurDir => {}
+            Component::RootDir => normalized = PathBuf::from("/"),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+        }
+    }
+    normalized
+}
+
+/// Metadata of an entry in an [`InMemorySys`].
+#[derive(Debug, Clone, Copy)]
+pub struct InMemoryMetadata {
+    is_symlink: bool,
+    is_file: bool,
+}
+
+impl SysMetadata for InMemoryMetadata {
+    fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    fn is_file(&self) -> bool {
+        self.is_file
+    }
+}
+
+/// An entry returned by [`InMemorySys`]'s `read_dir`.
+#[derive(Debug, Clone)]
+pub struct InMemoryDirEntry {
+    path: PathBuf,
+}
+
+impl SysReadDirEntry for InMemoryDirEntry {
+    fn file_name(&self) -> OsString {
+        self.path.file_name().unwrap_or_default().to_os_string()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+}
+
+impl Sys for InMemorySys {
+    type ReadDirEntry = InMemoryDirEntry;
+    type Metadata = InMemoryMetadata;
+
+    fn is_windows(&self) -> bool {
+        self.is_windows
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.cwd.clone())
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        self.home_dir.clone()
+    }
+
+    fn env_split_paths(&self, paths: &OsStr) -> Vec<PathBuf> {
+        paths
+            .to_string_lossy()
+            .split(if self.is_windows { ';' } else { ':' })
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    fn env_path(&self) -> Option<OsString> {
+        self.env_var_os(OsStr::new("PATH"))
+    }
+
+    fn env_path_ext(&self) -> Option<OsString> {
+        self.env_var_os(OsStr::new("PATHEXT"))
+    }
+
+    fn env_var_os(&self, name: &OsStr) -> Option<OsString> {
+        self.env_var_key(name).map(|key| self.env_vars[key].clone())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
+        let node = self.node(path, true)?;
+        Ok(InMemoryMetadata {
+            is_symlink: false,
+            is_file: matches!(node, Node::File { .. }),
+        })
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
+        let node = self.node(path, false)?;
+        Ok(InMemoryMetadata {
+            is_symlink: matches!(node, Node::Symlink { .. }),
+            is_file: matches!(node, Node::File { .. }),
+        })
+    }
+
+    fn read_dir(
+        &self,
+        path: &Path,
+    ) -> io::Result<Box<dyn Iterator<Item = io::Result<Self::ReadDirEntry>>>> {
+        let key = self.resolve(path, true)?;
+        if !matches!(self.entries.get(&key), None | Some(Node::Directory)) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("not a directory: {}", path.display()),
+            ));
+        }
+        let entries = self
+            .entries
+            .range(key.clone()..)
+            .map(|(p, _)| p)
+            .take_while(|p| p.starts_with(&key))
+            .filter(|p| p.parent() == Some(&key))
+            .map(|p| {
+                Ok(InMemoryDirEntry {
+                    path: path.join(p.file_name().unwrap_or_default()),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn is_valid_executable(&self, path: &Path) -> io::Result<bool> {
+        Ok(matches!(
+            self.node(path, true)?,
+            Node::File {
+                executable: true,
+                ..
+            }
+        ))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve(path, true)
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.node(path, true)? {
+            Node::File { contents, .. } => Ok(contents.clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("is a directory: {}", path.display()),
+            )),
+        }
+    }
+}
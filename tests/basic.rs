@@ -494,6 +494,187 @@ mod real_sys {
         assert!(found.len() == 1, "results contained {found:?}")
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_lint_path_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let f = TestFixture::new();
+        let dir = f.tempdir.path().join("a");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        let diagnostics = which::WhichConfig::new()
+            .custom_path_list(dir.into_os_string())
+            .lint_path()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(
+            diagnostics[0].kind,
+            which::PathDiagnosticKind::InsecurePermissions { .. }
+        ));
+        assert_eq!(diagnostics[0].severity(), which::Severity::Error);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn caching_resolver_notices_new_commands() {
+        let f = TestFixture::new();
+        let resolver = which::WhichConfig::new()
+            .custom_path_list(f.paths.clone())
+            .caching_resolver();
+        assert!(resolver.resolve("fresh").is_err());
+        let fresh = mk_bin(&f.tempdir.path().join("b"), "fresh", "").unwrap();
+        assert_eq!(
+            resolver.resolve("fresh").unwrap().canonicalize().unwrap(),
+            fresh
+        );
+        fs::remove_file(&fresh).unwrap();
+        assert!(resolver.resolve("fresh").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn caching_resolver_ignores_later_dirs() {
+        use std::cell::Cell;
+        use which::sys::Sys;
+        use which::{CandidateOutcome, Checker, DefaultChecker};
+
+        /// Counts the candidates checked, which only happens when the cache misses.
+        struct Counting<'a>(&'a Cell<usize>);
+
+        impl Checker for Counting<'_> {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                self.0.set(self.0.get() + 1);
+                DefaultChecker.check(sys, path)
+            }
+        }
+
+        let f = TestFixture::new();
+        let checks = Cell::new(0);
+        let resolver = which::WhichConfig::new()
+            .custom_path_list(f.paths.clone())
+            .checker(Counting(&checks))
+            .caching_resolver();
+        let first = resolver.resolve(BIN_NAME).unwrap();
+        assert_eq!(checks.get(), 1);
+        // A new file after the directory the result was found in can't change it.
+        mk_bin(&f.tempdir.path().join("c"), "other", "").unwrap();
+        assert_eq!(resolver.resolve(BIN_NAME).unwrap(), first);
+        assert_eq!(checks.get(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command_index() {
+        use std::cell::Cell;
+        use which::sys::Sys;
+        use which::{CandidateOutcome, Checker, DefaultChecker};
+
+        /// Counts the candidates checked. The index only needs to check the result.
+        struct Counting<'a>(&'a Cell<usize>);
+
+        impl Checker for Counting<'_> {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                self.0.set(self.0.get() + 1);
+                DefaultChecker.check(sys, path)
+            }
+        }
+
+        let f = TestFixture::new();
+        let plain = f.tempdir.path().join("a").join("plain");
+        fs::write(&plain, "").unwrap();
+        let later = f.tempdir.path().join("later");
+        let mut paths = env::split_paths(&f.paths).collect::<Vec<_>>();
+        paths.push(later.clone());
+        let paths = env::join_paths(paths).unwrap();
+        let location = f.tempdir.path().join("cache").join("command-index");
+        let checks = Cell::new(0);
+        let index = || {
+            which::WhichConfig::new()
+                .custom_path_list(paths.clone())
+                .checker(Counting(&checks))
+                .command_index_at(&location)
+        };
+
+        // Nothing is indexed yet, so lookups search as usual.
+        let first = index();
+        assert_eq!(first.location(), Some(location.as_path()));
+        assert_eq!(
+            first.resolve("bin").unwrap().canonicalize().unwrap(),
+            f.bins[0]
+        );
+        assert!(checks.get() > 0);
+        first.update().unwrap();
+        assert!(location.is_file());
+
+        checks.set(0);
+        let second = index();
+        assert_eq!(
+            second.resolve("bin").unwrap().canonicalize().unwrap(),
+            f.bins[0]
+        );
+        assert!(matches!(
+            second.resolve("missing"),
+            Err(which::Error::CannotFindBinaryPath(_))
+        ));
+        assert_eq!(checks.get(), 1);
+        let commands = second.commands().unwrap();
+        assert_eq!(commands[OsStr::new("bin")].len(), 3);
+        assert!(commands.contains_key(OsStr::new("win-bin.exe")));
+
+        assert!(matches!(
+            second.resolve("plain"),
+            Err(which::Error::CannotFindBinaryPath(_))
+        ));
+        assert!(!commands.contains_key(OsStr::new("plain")));
+
+        // The index is shared with other checkers, which still decide what matches.
+        let files = which::WhichConfig::new()
+            .custom_path_list(paths.clone())
+            .files()
+            .command_index_at(&location);
+        assert_eq!(files.resolve("plain").unwrap(), plain);
+        assert!(files.commands().unwrap().contains_key(OsStr::new("plain")));
+
+        // A directory that appeared since it was indexed is searched again.
+        fs::create_dir(&later).unwrap();
+        let fresh = mk_bin(&later, "fresh", "").unwrap();
+        assert_eq!(
+            second.resolve("fresh").unwrap().canonicalize().unwrap(),
+            fresh
+        );
+        assert!(checks.get() > 0);
+        second.update().unwrap();
+        checks.set(0);
+        assert_eq!(
+            index().resolve("fresh").unwrap().canonicalize().unwrap(),
+            fresh
+        );
+        assert_eq!(checks.get(), 1);
+    }
+
+    #[test]
+    fn replace_file_from_threads() {
+        use which::sys::{RealSys, Sys};
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("index");
+        let contents = (0..8u8).map(|i| vec![i; 1 << 16]).collect::<Vec<_>>();
+        std::thread::scope(|s| {
+            for contents in &contents {
+                let path = &path;
+                s.spawn(move || RealSys.replace_file(path, contents).unwrap());
+            }
+        });
+        // The file was written by one thread only, and no temporary file is left behind.
+        assert!(contents.contains(&fs::read(&path).unwrap()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // A directory can't be replaced, and the temporary file is removed again.
+        let subdir = dir.path().join("subdir");
+        fs::create_dir(&subdir).unwrap();
+        assert!(RealSys.replace_file(&subdir, b"").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
     #[test]
     #[cfg(windows)]
     fn windows_no_extension_but_executable() {
@@ -513,374 +694,1844 @@ mod real_sys {
 }
 
 mod in_memory {
-    use std::collections::BTreeMap;
-    use std::collections::HashMap;
-    use std::collections::HashSet;
-    use std::ffi::OsStr;
     use std::ffi::OsString;
-    use std::io;
-    use std::io::Error;
-    use std::io::ErrorKind;
-    use std::path::Component;
-    use std::path::Path;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+    use which::sys::memory::InMemorySys;
 
-    struct Metadata {
-        is_symlink: bool,
-        is_file: bool,
+    #[test]
+    fn basic() {
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/sub/dir1/:/sub/dir2/");
+        sys.write_file("/sub/dir1/exec1");
+        sys.write_executable("/sub/dir2/exec1"); // will get this one
+        sys.write_executable("/sub/dir2/exec2");
+        let config = which::WhichConfig::new_with_sys(sys).binary_name(OsString::from("exec1"));
+        let result = config.first_result().unwrap();
+        assert_eq!(result, PathBuf::from("/sub/dir2/exec1"));
     }
 
-    impl which::sys::SysMetadata for Metadata {
-        fn is_symlink(&self) -> bool {
-            self.is_symlink
-        }
+    #[test]
+    fn symlink() {
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/sub/dir1/");
+        sys.create_symlink("/sub/dir1/exec", "/sub/dir2/exec");
+        sys.write_executable("/sub/dir2/exec");
+        let config = which::WhichConfig::new_with_sys(sys).binary_name(OsString::from("exec"));
+        let result = config.first_result().unwrap();
+        assert_eq!(result, PathBuf::from("/sub/dir1/exec"));
+    }
 
-        fn is_file(&self) -> bool {
-            self.is_file
-        }
+    #[test]
+    fn tilde_path() {
+        let mut sys = InMemorySys::new();
+        sys.set_home("/home/user/");
+        sys.set_env_var("PATH", "/dir/:~/sub/");
+        sys.write_executable("/home/user/sub/exec");
+        let config = which::WhichConfig::new_with_sys(sys).binary_name(OsString::from("exec"));
+        let result = config.first_result().unwrap();
+        assert_eq!(result, PathBuf::from("/home/user/sub/exec"));
     }
 
-    struct ReadDirEntry {
-        path: PathBuf,
+    #[test]
+    fn not_found_context() {
+        let sys = InMemorySys::new()
+            .cwd("/project")
+            .home("/home/user/")
+            .env_var("PATH", "/usr/bin:~/bin")
+            .file("/usr/bin/exec");
+
+        let err = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("exec"))
+            .first_result()
+            .unwrap_err();
+        let which::Error::CannotFindBinaryPath(context) = &err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(context.query, Some(OsString::from("exec")));
+        assert_eq!(
+            context.paths,
+            vec![PathBuf::from("/usr/bin"), PathBuf::from("/home/user/bin")]
+        );
+        assert_eq!(context.cwd, None);
+        assert_eq!(
+            err.to_string(),
+            "cannot find binary `exec` in any of the searched directories: /usr/bin, /home/user/bin"
+        );
+
+        let err = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("./exec"))
+            .first_result()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot find binary `./exec` relative to /project"
+        );
+
+        let err = which::WhichConfig::new_with_sys(InMemorySys::new())
+            .binary_name(OsString::from("exec"))
+            .first_result()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            which::Error::CannotGetCurrentDirAndPathListEmpty(_)
+        ));
     }
 
-    impl which::sys::SysReadDirEntry for ReadDirEntry {
-        fn file_name(&self) -> OsString {
-            self.path.file_name().unwrap().to_os_string()
-        }
+    #[test]
+    fn memory_sys_paths() {
+        use which::sys::{Sys, SysMetadata};
+
+        let sys = InMemorySys::new()
+            .cwd("/work")
+            .executable("/opt/tool/bin/tool")
+            .symlink("/usr/bin/tool", "../../opt/tool/bin/tool")
+            .symlink("/opt/current", "tool")
+            .symlink("/loop", "/loop")
+            .file("notes.txt");
+        assert_eq!(
+            sys.canonicalize(Path::new("/usr/bin/tool")).unwrap(),
+            PathBuf::from("/opt/tool/bin/tool")
+        );
+        assert_eq!(
+            sys.canonicalize(Path::new("/opt/current/bin/./tool"))
+                .unwrap(),
+            PathBuf::from("/opt/tool/bin/tool")
+        );
+        assert!(sys.is_valid_executable(Path::new("/usr/bin/tool")).unwrap());
+        assert!(sys
+            .symlink_metadata(Path::new("/usr/bin/tool"))
+            .unwrap()
+            .is_symlink());
+        assert!(sys.metadata(Path::new("/usr/bin/tool")).unwrap().is_file());
+        assert!(sys.metadata(Path::new("/opt/current")).unwrap().is_dir());
+        assert!(sys.metadata(Path::new("/loop")).is_err());
+        assert!(sys.metadata(Path::new("/work/notes.txt/x")).is_err());
+        assert!(!sys.is_valid_executable(Path::new("notes.txt")).unwrap());
+        assert_eq!(
+            sys.read_dir(Path::new("/opt"))
+                .unwrap()
+                .map(|e| which::sys::SysReadDirEntry::path(&e.unwrap()))
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/opt/current"), PathBuf::from("/opt/tool")]
+        );
+    }
 
-        fn path(&self) -> PathBuf {
-            self.path.clone()
-        }
+    #[test]
+    fn memory_sys_windows() {
+        use which::sys::Sys;
+
+        let sys = InMemorySys::from_fixture(
+            r"
+            windows
+            # Names are case insensitive, and PATHEXT has a default.
+            env Path=/Tools;/windows
+            executable /tools/Node.EXE
+            ",
+        )
+        .unwrap();
+        assert!(sys.is_windows());
+        assert_eq!(sys.env_path(), Some(OsString::from("/Tools;/windows")));
+        assert_eq!(
+            sys.env_path_ext(),
+            Some(OsString::from(".COM;.EXE;.BAT;.CMD"))
+        );
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("node"))
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/Tools/Node.EXE"));
+
+        let err = InMemorySys::from_fixture("cwd /\nwindows").unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = InMemorySys::from_fixture("file /a\nexecutable /a/b").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fixture line 2: a parent is a file or symlink"
+        );
     }
 
-    #[derive(Debug, Clone)]
-    enum DirectoryEntry {
-        Directory(Directory),
-        File(File),
-        Symlink(Symlink),
+    #[test]
+    fn env_override() {
+        use which::sys::{EnvOverride, Sys};
+
+        let sys = InMemorySys::new()
+            .cwd("/project")
+            .home("/home/user")
+            .env_var("PATH", "/usr/bin")
+            .executable("/usr/bin/tool")
+            .executable("/opt/bin/tool")
+            .executable("/home/other/bin/tool")
+            .executable("/src/bin/tool");
+
+        let child = EnvOverride::from_envs(&sys, [("PATH", "/opt/bin:/usr/bin")]);
+        let result = which::WhichConfig::new_with_sys(&child)
+            .binary_name(OsString::from("tool"))
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/opt/bin/tool"));
+
+        let child = EnvOverride::new(&sys)
+            .env("PATH", "~/bin")
+            .env("HOME", "/home/other");
+        assert_eq!(child.home_dir(), Some(PathBuf::from("/home/other")));
+        let result = which::WhichConfig::new_with_sys(&child)
+            .binary_name(OsString::from("tool"))
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/home/other/bin/tool"));
+
+        let child = EnvOverride::new(&sys).current_dir("/src");
+        let result = which::WhichConfig::new_with_sys(&child)
+            .binary_name(OsString::from("bin/tool"))
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/src/bin/tool"));
+
+        let child = EnvOverride::new(&sys).env_clear().env("PATHEXT", ".EXE");
+        assert_eq!(child.env_path(), None);
+        assert_eq!(child.home_dir(), None);
+        assert_eq!(child.env_path_ext(), Some(OsString::from(".EXE")));
+        // The wrapped Sys is left alone.
+        assert_eq!(sys.env_path(), Some(OsString::from("/usr/bin")));
+
+        // Names are case insensitive when emulating Windows.
+        let windows = InMemorySys::windows().env_var("Path", "/windows");
+        let child = EnvOverride::new(&windows).env("PATH", "/tools");
+        assert_eq!(child.env_path(), Some(OsString::from("/tools")));
+        let child = child.env_remove("path");
+        assert_eq!(child.env_path(), None);
     }
 
-    impl DirectoryEntry {
-        pub fn unwrap_directory(&self) -> &Directory {
-            match self {
-                DirectoryEntry::Directory(d) => d,
-                _ => unreachable!(),
+    #[test]
+    fn path_extensions() {
+        use which::PathExtensions;
+
+        let sys = InMemorySys::windows()
+            .env_var("PATH", "/tools")
+            .env_var("PATHEXT", ".EXE")
+            .executable("/tools/build")
+            .executable("/tools/build.ps1")
+            .executable("/tools/build.exe");
+        let find = |path_extensions: Option<PathExtensions>| {
+            let mut nonfatal_errors = Vec::new();
+            let mut config = which::WhichConfig::new_with_sys(&sys)
+                .binary_name(OsString::from("build"))
+                .nonfatal_error_handler(|e| nonfatal_errors.push(e));
+            if let Some(path_extensions) = path_extensions {
+                config = config.path_extensions(path_extensions);
             }
-        }
+            let results = config.all_results().unwrap().collect::<Vec<_>>();
+            (results, nonfatal_errors.len())
+        };
+
+        assert_eq!(
+            find(None),
+            (
+                vec![
+                    PathBuf::from("/tools/build"),
+                    PathBuf::from("/tools/build.exe")
+                ],
+                0
+            )
+        );
+        assert_eq!(
+            find(Some(PathExtensions::Append(vec!["ps1".into()]))),
+            (
+                vec![
+                    PathBuf::from("/tools/build"),
+                    PathBuf::from("/tools/build.exe"),
+                    PathBuf::from("/tools/build.ps1")
+                ],
+                0
+            )
+        );
+        assert_eq!(
+            find(Some(PathExtensions::Replace(vec![".PS1".into()]))),
+            (
+                vec![
+                    PathBuf::from("/tools/build"),
+                    PathBuf::from("/tools/build.ps1")
+                ],
+                0
+            )
+        );
+        assert_eq!(
+            find(Some(PathExtensions::Clear)),
+            (vec![PathBuf::from("/tools/build")], 0)
+        );
 
-        pub fn unwrap_directory_mut(&mut self) -> &mut Directory {
-            match self {
-                DirectoryEntry::Directory(d) => d,
-                _ => unreachable!(),
+        // Suggestions and shadowing use the same extensions.
+        let suggestions = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("biuld"))
+            .path_extensions(PathExtensions::Replace(vec![".PS1".into()]))
+            .suggestions()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.path)
+            .collect::<Vec<_>>();
+        assert_eq!(suggestions, vec![PathBuf::from("/tools/build")]);
+    }
+
+    #[test]
+    fn prepend_append_exclude_paths() {
+        use which::ExcludePath;
+
+        let sys = InMemorySys::from_fixture(
+            r"
+            home /home/user
+            env PATH=/usr/bin:/mnt/c/Windows:/mnt/c/tools
+            executable /opt/bin/tool
+            executable /usr/bin/tool
+            executable /mnt/c/Windows/tool
+            executable /mnt/c/tools/tool
+            executable /mnt/cdrom/tool
+            executable /home/user/.cargo/bin/tool
+            symlink /usr/local/bin -> /usr/bin
+            ",
+        )
+        .unwrap();
+        let all = |config: which::WhichConfig<&InMemorySys>| {
+            config
+                .binary_name(OsString::from("tool"))
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            all(which::WhichConfig::new_with_sys(&sys)
+                .prepend_paths(["/opt/bin"])
+                .append_paths(["~/.cargo/bin", "/mnt/cdrom"])
+                .exclude_path(ExcludePath::Prefix("/mnt/c".into()))
+                .custom_path_list("/usr/local/bin:/mnt/c/tools".into())
+                .system_path_list()),
+            vec![
+                PathBuf::from("/opt/bin/tool"),
+                PathBuf::from("/usr/bin/tool"),
+                PathBuf::from("/home/user/.cargo/bin/tool"),
+                PathBuf::from("/mnt/cdrom/tool"),
+            ]
+        );
+        assert_eq!(
+            all(which::WhichConfig::new_with_sys(&sys)
+                .custom_path_list("/usr/local/bin:/mnt/c/tools".into())
+                .exclude_path(ExcludePath::Exact("/mnt/c".into()))
+                .exclude_path(ExcludePath::Exact("/usr/bin".into()))),
+            vec![
+                PathBuf::from("/usr/local/bin/tool"),
+                PathBuf::from("/mnt/c/tools/tool"),
+            ]
+        );
+        assert_eq!(
+            all(which::WhichConfig::new_with_sys(&sys)
+                .custom_path_list("/usr/local/bin:/mnt/c/tools".into())
+                .exclude_path(ExcludePath::Canonical("/usr/bin".into()))),
+            vec![PathBuf::from("/mnt/c/tools/tool")]
+        );
+
+        // Added directories are searched even without a path list.
+        let result =
+            which::WhichConfig::new_with_sys(InMemorySys::new().executable("/opt/bin/tool"))
+                .binary_name(OsString::from("tool"))
+                .append_paths(["/opt/bin"])
+                .first_result()
+                .unwrap();
+        assert_eq!(result, PathBuf::from("/opt/bin/tool"));
+    }
+
+    #[test]
+    fn filter() {
+        use which::{CandidateOutcome, NonFatalError};
+
+        let sys = InMemorySys::from_fixture(
+            r"
+            cwd /project
+            env PATH=/project/node_modules/.bin:/usr/bin
+            executable /project/node_modules/.bin/tsc
+            executable /usr/bin/tsc
+            ",
+        )
+        .unwrap();
+        let not_vendored =
+            |path: &Path| !path.components().any(|c| c.as_os_str() == "node_modules");
+
+        let mut rejected = Vec::new();
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tsc"))
+            .filter(not_vendored)
+            .nonfatal_error_handler(|e| {
+                if let NonFatalError::RejectedByFilter(path) = e {
+                    rejected.push(path);
+                }
+            })
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/usr/bin/tsc"));
+        assert_eq!(
+            rejected,
+            vec![PathBuf::from("/project/node_modules/.bin/tsc")]
+        );
+
+        let explanation = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tsc"))
+            .filter(not_vendored)
+            .explain()
+            .unwrap();
+        assert!(matches!(
+            explanation.candidates[0].outcome,
+            CandidateOutcome::Filtered
+        ));
+        assert_eq!(
+            explanation.matches().map(|c| &c.path).collect::<Vec<_>>(),
+            vec![&PathBuf::from("/usr/bin/tsc")]
+        );
+
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tsc"))
+            .filter(|_: &Path| false)
+            .first_result();
+        assert!(matches!(result, Err(which::Error::CannotFindBinaryPath(_))));
+    }
+
+    #[test]
+    fn checker() {
+        use which::sys::{Sys, SysMetadata};
+        use which::{CandidateOutcome, Checker};
+
+        struct AnyFile;
+
+        impl Checker for AnyFile {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                match sys.metadata(path) {
+                    Ok(metadata) if metadata.is_file() => CandidateOutcome::Match,
+                    Ok(_) => CandidateOutcome::NotAFile,
+                    Err(e) => CandidateOutcome::Missing(e),
+                }
             }
         }
 
-        pub fn as_metadata(&self) -> Metadata {
-            Metadata {
-                is_symlink: matches!(self, DirectoryEntry::Symlink(_)),
-                is_file: matches!(self, DirectoryEntry::File(_)),
+        struct NotInOpt;
+
+        impl Checker for NotInOpt {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                if path.starts_with("/opt") {
+                    CandidateOutcome::Rejected("untrusted directory".into())
+                } else {
+                    which::DefaultChecker.check(sys, path)
+                }
             }
         }
-    }
 
-    #[derive(Debug, Default, Clone)]
-    struct Directory {
-        entries: BTreeMap<OsString, DirectoryEntry>,
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/opt/bin:/usr/share/man/man1:/usr/bin
+            executable /opt/bin/ls
+            file /usr/share/man/man1/ls
+            executable /usr/bin/ls
+            ",
+        )
+        .unwrap();
+        let config = || which::WhichConfig::new_with_sys(&sys).binary_name(OsString::from("ls"));
+
+        assert_eq!(
+            config()
+                .checker(AnyFile)
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/opt/bin/ls"),
+                PathBuf::from("/usr/share/man/man1/ls"),
+                PathBuf::from("/usr/bin/ls"),
+            ]
+        );
+        assert_eq!(
+            config().checker(NotInOpt).first_result().unwrap(),
+            PathBuf::from("/usr/bin/ls")
+        );
+
+        let explanation = config().checker(NotInOpt).explain().unwrap();
+        assert!(matches!(
+            &explanation.candidates[0].outcome,
+            CandidateOutcome::Rejected(reason) if reason == "untrusted directory"
+        ));
+        assert!(matches!(
+            explanation.candidates[1].outcome,
+            CandidateOutcome::NotExecutable(_)
+        ));
     }
 
-    #[derive(Debug, Clone)]
-    struct File {
-        is_valid_executable: bool,
+    #[test]
+    fn search_files() {
+        use which::sys::EnvOverride;
+
+        let sys = InMemorySys::from_fixture(
+            r"
+            home /home/user
+            env MANPATH=~/.local/share/man:/usr/share/man
+            env PYTHONPATH=/srv/app:/usr/lib/python3
+            file /usr/share/man/man1/ls.1
+            file /usr/share/man/man8/mount.8
+            file /usr/lib/python3/json.py
+            directory /srv/app/json.py
+            ",
+        )
+        .unwrap();
+
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .path_list_var("MANPATH")
+            .subpaths(["man1", "man8"])
+            .files()
+            .binary_name(OsString::from("mount.8"))
+            .explain()
+            .unwrap();
+        assert_eq!(
+            result.matches().map(|c| &c.path).collect::<Vec<_>>(),
+            vec![&PathBuf::from("/usr/share/man/man8/mount.8")]
+        );
+        assert_eq!(
+            result
+                .candidates
+                .iter()
+                .map(|c| (c.entry_index, c.path.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Some(0),
+                    PathBuf::from("/home/user/.local/share/man/man1/mount.8")
+                ),
+                (
+                    Some(0),
+                    PathBuf::from("/home/user/.local/share/man/man8/mount.8")
+                ),
+                (Some(1), PathBuf::from("/usr/share/man/man1/mount.8")),
+                (Some(1), PathBuf::from("/usr/share/man/man8/mount.8")),
+            ]
+        );
+
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .path_list_var("PYTHONPATH")
+            .files()
+            .binary_name(OsString::from("json.py"))
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/usr/lib/python3/json.py"));
+
+        // The default checker still wants an executable.
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .path_list_var("PYTHONPATH")
+            .binary_name(OsString::from("json.py"))
+            .first_result();
+        assert!(result.is_err());
+
+        let result =
+            which::WhichConfig::new_with_sys(EnvOverride::new(&sys).env_remove("PYTHONPATH"))
+                .path_list_var("PYTHONPATH")
+                .files()
+                .binary_name(OsString::from("json.py"))
+                .first_result();
+        assert!(matches!(
+            result,
+            Err(which::Error::CannotGetCurrentDirAndPathListEmpty(_))
+        ));
     }
 
-    #[derive(Debug, Clone)]
-    struct Symlink {
-        to: PathBuf,
+    #[test]
+    fn shadowing() {
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/usr/local/bin:/usr/bin:/bin");
+        sys.write_executable("/usr/local/bin/python3");
+        sys.write_executable("/usr/bin/python3");
+        sys.write_file("/bin/python3");
+        let shadowing = which::WhichConfig::new_with_sys(sys)
+            .binary_name(OsString::from("python3"))
+            .shadowing()
+            .unwrap();
+        assert_eq!(shadowing.active, PathBuf::from("/usr/local/bin/python3"));
+        assert_eq!(shadowing.shadowed, vec![PathBuf::from("/usr/bin/python3")]);
     }
 
-    #[derive(Debug, Clone)]
-    struct InMemorySys {
-        is_windows: bool,
-        cwd: PathBuf,
-        home_dir: Option<PathBuf>,
-        env_vars: HashMap<OsString, OsString>,
-        root_dir: DirectoryEntry,
+    #[test]
+    fn all_shadowed() {
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/usr/local/bin:/usr/bin:/usr/local/bin:/bin");
+        sys.write_executable("/usr/local/bin/python3");
+        sys.write_executable("/usr/local/bin/unique");
+        sys.write_executable("/usr/bin/python3");
+        sys.write_executable("/usr/bin/git");
+        sys.write_executable("/bin/git");
+        sys.write_file("/bin/unique");
+        sys.create_directory("/bin/python3");
+        let shadowed = which::WhichConfig::new_with_sys(sys)
+            .all_shadowed()
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.name, s.active, s.shadowed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shadowed,
+            vec![
+                (
+                    OsString::from("git"),
+                    PathBuf::from("/usr/bin/git"),
+                    vec![PathBuf::from("/bin/git")]
+                ),
+                (
+                    OsString::from("python3"),
+                    PathBuf::from("/usr/local/bin/python3"),
+                    vec![PathBuf::from("/usr/bin/python3")]
+                ),
+            ]
+        );
     }
 
-    impl InMemorySys {
-        pub fn new() -> Self {
-            Self {
-                is_windows: false,
-                cwd: PathBuf::from("/project"),
-                home_dir: None,
-                env_vars: Default::default(),
-                root_dir: DirectoryEntry::Directory(Directory::default()),
-            }
-        }
+    #[test]
+    fn all_shadowed_windows() {
+        let mut sys = InMemorySys::windows();
+        sys.set_env_var("PATH", "/tools;/windows");
+        sys.set_env_var("PATHEXT", ".COM;.EXE;.CMD");
+        sys.write_executable("/tools/node.cmd");
+        sys.write_executable("/tools/node.txt");
+        sys.write_executable("/windows/NODE.EXE");
+        sys.write_executable("/windows/node.txt");
+        let shadowed = which::WhichConfig::new_with_sys(sys)
+            .all_shadowed()
+            .unwrap();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].name, OsString::from("node"));
+        assert_eq!(shadowed[0].active, PathBuf::from("/tools/node.cmd"));
+        assert_eq!(
+            shadowed[0].shadowed,
+            vec![PathBuf::from("/windows/NODE.EXE")]
+        );
+    }
 
-        pub fn set_home_dir(&mut self, path: impl AsRef<Path>) {
-            self.home_dir = Some(path.as_ref().to_path_buf());
-        }
+    #[test]
+    fn suggestions() {
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/usr/local/bin:/usr/bin");
+        sys.write_executable("/usr/local/bin/gitk");
+        sys.write_executable("/usr/bin/git");
+        sys.write_executable("/usr/bin/gitk");
+        sys.write_executable("/usr/bin/gzip");
+        sys.write_file("/usr/bin/gti2");
+        let suggestions = which::WhichConfig::new_with_sys(sys)
+            .binary_name(OsString::from("gti"))
+            .suggestions()
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.name, s.path, s.distance))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            suggestions,
+            vec![(OsString::from("git"), PathBuf::from("/usr/bin/git"), 1)]
+        );
 
-        pub fn set_env_var(&mut self, name: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
-            self.env_vars
-                .insert(name.as_ref().to_os_string(), value.as_ref().to_os_string());
-        }
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/usr/local/bin:/usr/bin");
+        sys.write_executable("/usr/local/bin/pyhton3");
+        sys.write_executable("/usr/bin/python3");
+        sys.write_executable("/usr/bin/python");
+        let suggestions = which::WhichConfig::new_with_sys(sys)
+            .binary_name(OsString::from("pyton3"))
+            .suggestions()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            suggestions,
+            vec![
+                PathBuf::from("/usr/local/bin/pyhton3"),
+                PathBuf::from("/usr/bin/python3"),
+                PathBuf::from("/usr/bin/python"),
+            ]
+        );
+    }
 
-        pub fn create_symlink(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) {
-            self.insert_dir_entry(
-                from,
-                DirectoryEntry::Symlink(Symlink {
-                    to: to.as_ref().to_path_buf(),
-                }),
-            );
-        }
+    #[test]
+    fn suggestions_windows() {
+        let mut sys = InMemorySys::windows();
+        sys.set_env_var("PATH", "/tools;/windows");
+        sys.set_env_var("PATHEXT", ".COM;.EXE;.CMD");
+        sys.write_executable("/tools/Node.cmd");
+        sys.write_executable("/windows/node.exe");
+        sys.write_executable("/windows/nose.txt");
+        let suggestions = which::WhichConfig::new_with_sys(sys)
+            .binary_name(OsString::from("NOED"))
+            .suggestions()
+            .unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].name, OsString::from("Node"));
+        assert_eq!(suggestions[0].path, PathBuf::from("/tools/Node.cmd"));
+        assert_eq!(suggestions[0].distance, 1);
+    }
 
-        pub fn write_executable(&mut self, path: impl AsRef<Path>) {
-            self.insert_dir_entry(
-                path,
-                DirectoryEntry::File(File {
-                    is_valid_executable: true,
-                }),
-            );
-        }
+    #[test]
+    fn refuse_relative_path_entries() {
+        let sys = InMemorySys::from_fixture(
+            r"
+            cwd /project
+            env PATH=bin::/usr/bin
+            executable /project/bin/tool
+            executable /project/tool
+            executable /usr/bin/tool
+            ",
+        )
+        .unwrap();
 
-        pub fn write_non_executable(&mut self, path: impl AsRef<Path>) {
-            self.insert_dir_entry(
-                path,
-                DirectoryEntry::File(File {
-                    is_valid_executable: false,
-                }),
-            );
-        }
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tool"))
+            .first_result();
+        assert_eq!(result.unwrap(), PathBuf::from("bin/tool"));
 
-        fn insert_dir_entry(&mut self, path: impl AsRef<Path>, entry: DirectoryEntry) {
-            // not super efficient, but good enough for testing
-            let dir_path = path.as_ref().parent().unwrap();
-            self.create_directory(dir_path);
-            let dir_entry = self.with_entry_mut(dir_path).unwrap();
-            let dir = dir_entry.unwrap_directory_mut();
-            let name = path.as_ref().file_name().unwrap();
-            dir.entries.insert(name.to_os_string(), entry);
-        }
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tool"))
+            .refuse_relative_path_entries(true)
+            .first_result();
+        assert!(
+            matches!(result, Err(which::Error::FoundRelativeToCwd(path)) if path == Path::new("bin/tool"))
+        );
 
-        pub fn create_directory(&mut self, path: impl AsRef<Path>) {
-            // lazy implementation
-            let mut ancestors = path
-                .as_ref()
-                .ancestors()
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev()
-                .peekable();
-            while let Some(ancestor) = ancestors.next() {
-                let entry = self.with_entry_mut(ancestor).unwrap();
-                match entry {
-                    DirectoryEntry::Directory(d) => {
-                        if let Some(next_name) = ancestors.peek().and_then(|a| a.file_name()) {
-                            if !d.entries.contains_key(next_name) {
-                                d.entries.insert(
-                                    next_name.to_os_string(),
-                                    DirectoryEntry::Directory(Default::default()),
-                                );
-                            }
-                        }
-                    }
-                    _ => unreachable!("Not a directory."),
+        let mut refused = Vec::new();
+        let results = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tool"))
+            .refuse_relative_path_entries(true)
+            .nonfatal_error_handler(|e| {
+                if let which::NonFatalError::FoundRelativeToCwd(path) = e {
+                    refused.push(path);
                 }
-            }
-        }
+            })
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(results, vec![PathBuf::from("/usr/bin/tool")]);
+        assert_eq!(
+            refused,
+            vec![PathBuf::from("bin/tool"), PathBuf::from("tool")]
+        );
 
-        fn with_entry_mut(&mut self, path: impl AsRef<Path>) -> Option<&mut DirectoryEntry> {
-            let mut current_entry = &mut self.root_dir;
-            let mut components = path.as_ref().components().peekable();
-
-            while let Some(component) = components.next() {
-                match component {
-                    Component::RootDir => {
-                        let is_last = components.peek().is_none();
-                        if is_last {
-                            return Some(current_entry);
-                        }
-                    }
-                    Component::Normal(os_str) => {
-                        let is_last = components.peek().is_none();
-                        let entry = current_entry
-                            .unwrap_directory_mut()
-                            .entries
-                            .get_mut(os_str)?;
-
-                        if is_last {
-                            return Some(entry);
-                        }
-
-                        current_entry = entry;
+        let explanation = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tool"))
+            .refuse_relative_path_entries(true)
+            .explain()
+            .unwrap();
+        assert!(matches!(
+            explanation.candidates[0].outcome,
+            which::CandidateOutcome::RelativePathEntry
+        ));
+        assert_eq!(
+            explanation.matches().map(|c| &c.path).collect::<Vec<_>>(),
+            vec![Path::new("/usr/bin/tool")]
+        );
+
+        let shadowed = which::WhichConfig::new_with_sys(&sys)
+            .refuse_relative_path_entries(true)
+            .all_shadowed()
+            .unwrap();
+        assert!(shadowed.is_empty());
+
+        let suggestions = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("tol"))
+            .refuse_relative_path_entries(true)
+            .suggestions()
+            .unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].path, PathBuf::from("/usr/bin/tool"));
+
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .glob("t??l")
+            .refuse_relative_path_entries(true)
+            .first_result();
+        assert!(
+            matches!(result, Err(which::Error::FoundRelativeToCwd(path)) if path == Path::new("bin/tool"))
+        );
+
+        // Explicitly relative queries aren't affected.
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .binary_name(OsString::from("./tool"))
+            .refuse_relative_path_entries(true)
+            .first_result();
+        assert_eq!(result.unwrap(), PathBuf::from("/project/tool"));
+    }
+
+    #[test]
+    fn lint_path() {
+        use which::{PathDiagnosticKind, Severity};
+
+        let mut sys = InMemorySys::new();
+        sys.set_home("/home/user");
+        sys.set_env_var(
+            "PATH",
+            "/usr/bin::bin:~/bin:/missing:/usr/bin/:/usr/bin/tool:/link",
+        );
+        sys.write_executable("/usr/bin/tool");
+        sys.create_directory("/home/user/bin");
+        sys.create_symlink("/link", "/usr/bin");
+        let diagnostics = which::WhichConfig::new_with_sys(sys)
+            .lint_path()
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.index, d.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (1, PathDiagnosticKind::Empty),
+                (2, PathDiagnosticKind::Relative),
+                (3, PathDiagnosticKind::UnexpandedTilde),
+                (4, PathDiagnosticKind::Missing),
+                (5, PathDiagnosticKind::Duplicate { first: 0 }),
+                (6, PathDiagnosticKind::NotADirectory),
+                (
+                    7,
+                    PathDiagnosticKind::DuplicateAfterCanonicalization {
+                        first: 0,
+                        canonical: PathBuf::from("/usr/bin")
                     }
-                    Component::CurDir | Component::ParentDir | Component::Prefix(_) => todo!(),
-                }
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[5].1.severity(), Severity::Error);
+    }
+
+    #[test]
+    fn explain() {
+        let mut sys = InMemorySys::new();
+        sys.set_env_var("PATH", "/missing/:~/bin/:/sub/dir1/:/sub/dir2/:/sub/dir3/");
+        sys.create_directory("/sub/dir1/exec");
+        sys.write_file("/sub/dir2/exec");
+        sys.write_executable("/sub/dir3/exec");
+        let explanation = which::WhichConfig::new_with_sys(sys)
+            .binary_name(OsString::from("exec"))
+            .explain()
+            .unwrap();
+
+        let candidates = &explanation.candidates;
+        assert_eq!(candidates.len(), 5);
+        assert_eq!(candidates[1].entry_index, Some(1));
+        assert!(candidates[1].tilde_expansion_failed);
+        assert_eq!(candidates[3].directory, PathBuf::from("/sub/dir2/"));
+        assert_eq!(candidates[3].path, PathBuf::from("/sub/dir2/exec"));
+        assert!(matches!(
+            candidates[0].outcome,
+            which::CandidateOutcome::Missing(_)
+        ));
+        assert!(matches!(
+            candidates[2].outcome,
+            which::CandidateOutcome::NotAFile
+        ));
+        assert!(matches!(
+            candidates[3].outcome,
+            which::CandidateOutcome::NotExecutable(_)
+        ));
+        assert_eq!(
+            explanation.matches().map(|c| &c.path).collect::<Vec<_>>(),
+            vec![&PathBuf::from("/sub/dir3/exec")]
+        );
+    }
+
+    #[test]
+    fn explain_path_ext() {
+        let mut sys = InMemorySys::windows();
+        sys.set_env_var("PATH", "/sub/dir1/");
+        sys.set_env_var("PATHEXT", ".COM;.EXE");
+        sys.write_executable("/sub/dir1/exec.EXE");
+        let explanation = which::WhichConfig::new_with_sys(sys)
+            .binary_name(OsString::from("exec"))
+            .explain()
+            .unwrap();
+
+        let extensions = explanation
+            .candidates
+            .iter()
+            .map(|c| c.extension.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(extensions, vec![None, Some(".COM"), Some(".EXE")]);
+        assert!(explanation.candidates[2].outcome.is_match());
+        assert!(!explanation.path_ext_not_populated);
+    }
+
+    const EM_X86_64: u16 = 62;
+    const EM_AARCH64: u16 = 183;
+
+    /// Builds a minimal little-endian ELF64 shared object with the given `DT_RPATH` and
+    /// `DT_RUNPATH`.
+    fn elf(machine: u16, rpath: Option<&str>, runpath: Option<&str>) -> Vec<u8> {
+        const DYNAMIC: usize = 64 + 2 * 56;
+        let mut strtab = vec![0];
+        let mut dynamic = vec![(5u64, 0u64)];
+        for (tag, value) in [(15, rpath), (29, runpath)] {
+            if let Some(value) = value {
+                dynamic.push((tag, strtab.len() as u64));
+                strtab.extend_from_slice(value.as_bytes());
+                strtab.push(0);
             }
-            None
         }
-
-        fn get_entry(&self, path: &Path) -> Option<&DirectoryEntry> {
-            let mut current_entry = &self.root_dir;
-            let mut components = path.components().peekable();
-
-            while let Some(component) = components.next() {
-                match component {
-                    Component::RootDir => continue,
-                    Component::Normal(os_str) => {
-                        let entry = current_entry.unwrap_directory().entries.get(os_str)?;
-                        if components.peek().is_none() {
-                            return Some(entry);
-                        } else {
-                            current_entry = entry;
-                        }
-                    }
-                    Component::CurDir | Component::ParentDir | Component::Prefix(_) => todo!(),
-                }
+        dynamic.push((0, 0));
+        let dynamic_size = dynamic.len() * 16;
+        dynamic[0].1 = (DYNAMIC + dynamic_size) as u64;
+        let size = DYNAMIC + dynamic_size + strtab.len();
+
+        let mut data = b"\x7fELF\x02\x01\x01".to_vec();
+        data.resize(16, 0);
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&machine.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&64u64.to_le_bytes());
+        data.extend_from_slice(&[0; 12]);
+        for half in [64u16, 56, 2, 0, 0, 0] {
+            data.extend_from_slice(&half.to_le_bytes());
+        }
+        for (kind, offset, filesz) in [(1u32, 0, size), (2, DYNAMIC, dynamic_size)] {
+            data.extend_from_slice(&kind.to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes());
+            for word in [offset, offset, offset, filesz, filesz, 8] {
+                data.extend_from_slice(&(word as u64).to_le_bytes());
             }
+        }
+        for (tag, value) in dynamic {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&strtab);
+        data
+    }
 
-            unreachable!()
+    /// `ld.so.cache` flags of libraries for x86-64, i386 and x32.
+    const FLAGS_X86_64: i32 = 0x0303;
+    const FLAGS_I386: i32 = 0x0003;
+    const FLAGS_X32: i32 = 0x0803;
+
+    /// Builds an `ld.so.cache` in the new format.
+    fn ld_so_cache(entries: &[(i32, &str, &str)]) -> Vec<u8> {
+        let mut strings = Vec::new();
+        let mut offsets = Vec::new();
+        let strings_start = 48 + entries.len() * 24;
+        for (_, name, path) in entries {
+            let key = strings_start + strings.len();
+            strings.extend_from_slice(name.as_bytes());
+            strings.push(0);
+            let value = strings_start + strings.len();
+            strings.extend_from_slice(path.as_bytes());
+            strings.push(0);
+            offsets.push((key as u32, value as u32));
+        }
+        let flags = entries.iter().map(|(flags, _, _)| *flags);
+        let mut data = b"glibc-ld.so.cache1.1".to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_ne_bytes());
+        data.extend_from_slice(&(strings.len() as u32).to_ne_bytes());
+        data.resize(48, 0);
+        for (flags, (key, value)) in flags.zip(offsets) {
+            data.extend_from_slice(&flags.to_ne_bytes());
+            data.extend_from_slice(&key.to_ne_bytes());
+            data.extend_from_slice(&value.to_ne_bytes());
+            data.extend_from_slice(&[0; 12]);
         }
+        data.extend_from_slice(&strings);
+        data
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn regex_checks() {
+        use regex::Regex;
+
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/usr/bin:/opt/bin
+            file /usr/bin/python2
+            executable /usr/bin/python3
+            executable /opt/bin/python3
+            directory /opt/bin/python4
+            ",
+        )
+        .unwrap();
+        let search = |checks| {
+            which::WhichConfig::new_with_sys(&sys)
+                .regex(Regex::new(r"^python\d$").unwrap())
+                .regex_checks(checks)
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            search(true),
+            vec![
+                PathBuf::from("/usr/bin/python3"),
+                PathBuf::from("/opt/bin/python3")
+            ]
+        );
+        assert_eq!(
+            search(false),
+            vec![
+                PathBuf::from("/usr/bin/python2"),
+                PathBuf::from("/usr/bin/python3"),
+                PathBuf::from("/opt/bin/python3"),
+                PathBuf::from("/opt/bin/python4")
+            ]
+        );
+
+        // On Windows the regex sees the name with and without its extension.
+        let sys = InMemorySys::windows()
+            .env_var("PATH", "/tools")
+            .executable("/tools/Python.EXE")
+            .executable("/tools/python.txt")
+            .executable("/tools/pythonw.exe");
+        let search = |regex: &str| {
+            which::WhichConfig::new_with_sys(&sys)
+                .regex(Regex::new(regex).unwrap())
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            search(r"^Python$"),
+            vec![PathBuf::from("/tools/Python.EXE")]
+        );
+        assert_eq!(search(r"\.exe$"), vec![PathBuf::from("/tools/pythonw.exe")]);
+        assert_eq!(
+            search(r"(?i)^python\.exe$"),
+            vec![PathBuf::from("/tools/Python.EXE")]
+        );
+        assert_eq!(search(r"\.txt$"), Vec::<PathBuf>::new());
+    }
 
-        fn get_entry_follow_symlink(&self, path: &Path) -> Option<&DirectoryEntry> {
-            let mut current_path = path.to_path_buf();
-            let mut seen = HashSet::new();
+    #[test]
+    #[cfg(feature = "regex")]
+    fn regex_with_cwd() {
+        use regex::Regex;
+
+        let sys = InMemorySys::from_fixture(
+            r"
+            cwd /src/app
+            env PATH=/usr/bin
+            executable /src/app/gradlew
+            file /src/app/gradlew.bat
+            executable /usr/bin/gradlew-wrapper
+            ",
+        )
+        .unwrap();
+        let search = |config: which::WhichConfig<&InMemorySys>| {
+            config
+                .regex(Regex::new(r"^gradlew").unwrap())
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
 
-            loop {
-                let entry = self.get_entry(&current_path)?;
-                if let DirectoryEntry::Symlink(symlink) = &entry {
-                    if !seen.insert(current_path.clone()) {
-                        return None; // symlink loop
-                    }
-                    current_path = symlink.to.clone();
-                    continue;
-                }
-                return Some(entry);
+        // The cwd is only searched when it was asked for, and comes first.
+        assert_eq!(
+            search(which::WhichConfig::new_with_sys(&sys)),
+            vec![PathBuf::from("/usr/bin/gradlew-wrapper")]
+        );
+        assert_eq!(
+            search(which::WhichConfig::new_with_sys(&sys).system_cwd(true)),
+            vec![
+                PathBuf::from("/src/app/gradlew"),
+                PathBuf::from("/usr/bin/gradlew-wrapper")
+            ]
+        );
+        assert_eq!(
+            search(
+                which::WhichConfig::new_with_sys(&sys)
+                    .custom_cwd(PathBuf::from("/src/app"))
+                    .custom_path_list(OsString::from("/opt/bin"))
+            ),
+            vec![PathBuf::from("/src/app/gradlew")]
+        );
+
+        // Setting the cwd after the regex works too.
+        let err = which::WhichConfig::new_with_sys(&sys)
+            .regex(Regex::new(r"^mvnw$").unwrap())
+            .custom_cwd(PathBuf::from("/src/app"))
+            .first_result()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot find binary `^mvnw$` relative to /src/app in any of the searched \
+             directories: /src/app, /usr/bin"
+        );
+        match err {
+            which::Error::CannotFindBinaryPath(context) => {
+                assert_eq!(context.query, Some(OsString::from("^mvnw$")));
+                assert_eq!(context.cwd, Some(PathBuf::from("/src/app")));
+                assert_eq!(
+                    context.paths,
+                    vec![PathBuf::from("/src/app"), PathBuf::from("/usr/bin")]
+                );
             }
+            e => panic!("unexpected error {e:?}"),
         }
     }
 
-    impl which::sys::Sys for InMemorySys {
-        type ReadDirEntry = ReadDirEntry;
+    #[test]
+    #[cfg(all(unix, feature = "regex"))]
+    fn regex_bytes_and_full_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let latin1 = Path::new("/usr/bin").join(OsStr::from_bytes(b"caf\xe9-tool"));
+        let sys = InMemorySys::new()
+            .env_var("PATH", "/usr/bin:/usr/local/bin")
+            .executable(&latin1)
+            .executable("/usr/local/bin/cafe-tool");
+
+        // A `Regex` can't see names that aren't valid UTF-8, a `bytes::Regex` can.
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .regex(regex::Regex::new(r"^caf.-tool$").unwrap())
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![PathBuf::from("/usr/local/bin/cafe-tool")]);
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .bytes_regex(regex::bytes::Regex::new(r"(?-u)^caf.-tool$").unwrap())
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![latin1.clone(), PathBuf::from("/usr/local/bin/cafe-tool")]
+        );
 
-        type Metadata = Metadata;
+        // The full path can be matched instead of the name.
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .regex(regex::Regex::new(r"^/usr/local/").unwrap())
+            .regex_full_path(true)
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![PathBuf::from("/usr/local/bin/cafe-tool")]);
+    }
 
-        fn is_windows(&self) -> bool {
-            self.is_windows
-        }
+    #[test]
+    fn glob() {
+        use std::cell::Cell;
+        use which::sys::Sys;
+        use which::{CandidateOutcome, Checker, DefaultChecker};
 
-        fn current_dir(&self) -> io::Result<PathBuf> {
-            Ok(self.cwd.clone())
-        }
+        struct Counting<'a>(&'a Cell<usize>);
 
-        fn home_dir(&self) -> Option<PathBuf> {
-            self.home_dir.clone()
+        impl Checker for Counting<'_> {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                self.0.set(self.0.get() + 1);
+                DefaultChecker.check(sys, path)
+            }
         }
 
-        fn env_split_paths(&self, paths: &OsStr) -> Vec<PathBuf> {
-            paths
-                .to_string_lossy()
-                .split(if self.is_windows { ";" } else { ":" })
-                .map(PathBuf::from)
-                .collect()
-        }
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/usr/bin:/opt/bin
+            executable /usr/bin/cargo-fmt
+            file /usr/bin/cargo-notes
+            executable /usr/bin/clang-9
+            executable /usr/bin/clang-14
+            executable /usr/bin/clang-18
+            executable /opt/bin/cargo-clippy
+            directory /opt/bin/cargo-registry
+            ",
+        )
+        .unwrap();
+        let search = |pattern: &str| {
+            which::WhichConfig::new_with_sys(&sys)
+                .glob(pattern)
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            search("cargo-*"),
+            vec![
+                PathBuf::from("/usr/bin/cargo-fmt"),
+                PathBuf::from("/opt/bin/cargo-clippy")
+            ]
+        );
+        assert_eq!(
+            search("clang-1[0-5]"),
+            vec![PathBuf::from("/usr/bin/clang-14")]
+        );
+        assert_eq!(search("clang-?"), vec![PathBuf::from("/usr/bin/clang-9")]);
+        let err = which::WhichConfig::new_with_sys(&sys)
+            .glob("gcc-*")
+            .first_result()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot find binary `gcc-*` in any of the searched directories: /usr/bin, /opt/bin"
+        );
+        // Case matters, except on Windows.
+        assert_eq!(search("CARGO-*"), Vec::<PathBuf>::new());
+
+        // Directories are read and checked lazily, so the first result doesn't need the rest.
+        let checks = Cell::new(0);
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .glob("cargo-*")
+            .checker(Counting(&checks))
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/usr/bin/cargo-fmt"));
+        assert_eq!(checks.get(), 1);
+
+        let sys = InMemorySys::windows()
+            .env_var("PATH", "/tools")
+            .executable("/tools/Cargo-Fmt.EXE")
+            .executable("/tools/cargo-notes.txt");
+        let search = |pattern: &str| {
+            which::WhichConfig::new_with_sys(&sys)
+                .glob(pattern)
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        let fmt = vec![PathBuf::from("/tools/Cargo-Fmt.EXE")];
+        assert_eq!(search("cargo-*"), fmt);
+        assert_eq!(search("*.exe"), fmt);
+        assert_eq!(search("cargo-fmt.exe"), fmt);
+        assert_eq!(search("*.txt"), Vec::<PathBuf>::new());
+
+        // Matches in relative path list entries can be refused.
+        let sys = InMemorySys::from_fixture(
+            r"
+            cwd /project
+            env PATH=bin:/usr/bin
+            executable /project/bin/ls
+            executable /usr/bin/ls
+            ",
+        )
+        .unwrap();
+        let mut refused = Vec::new();
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .glob("l?")
+            .refuse_relative_path_entries(true)
+            .nonfatal_error_handler(|e| {
+                if let which::NonFatalError::FoundRelativeToCwd(path) = e {
+                    refused.push(path);
+                }
+            })
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![PathBuf::from("/usr/bin/ls")]);
+        assert_eq!(refused, vec![PathBuf::from("bin/ls")]);
+    }
 
-        fn env_path(&self) -> Option<OsString> {
-            self.env_vars.get(OsStr::new("PATH")).cloned()
-        }
+    #[test]
+    fn query() {
+        use which::{Query, QueryScope};
 
-        fn env_path_ext(&self) -> Option<OsString> {
-            self.env_vars.get(OsStr::new("PATHEXT")).cloned()
+        let parse = |query: &str| {
+            let query = Query::parse(query).unwrap();
+            (query.scope, query.pattern)
+        };
+        assert_eq!(parse("java"), (QueryScope::PathList, "java".to_owned()));
+        assert_eq!(
+            parse("$JAVA_HOME:java*"),
+            (QueryScope::Var("JAVA_HOME".into()), "java*".to_owned())
+        );
+        assert_eq!(
+            parse(r"C:\tools:*.exe"),
+            (QueryScope::Dir(r"C:\tools".into()), "*.exe".to_owned())
+        );
+        assert_eq!(
+            parse("/opt/tools:bin/tool"),
+            (QueryScope::PathList, "/opt/tools:bin/tool".to_owned())
+        );
+        for invalid in ["", ":java", "$:java", "/opt/tools:"] {
+            assert!(matches!(
+                Query::parse(invalid),
+                Err(which::Error::InvalidQuery(_))
+            ));
         }
 
-        fn metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
-            let entry = self
-                .get_entry_follow_symlink(path)
-                .ok_or_else(|| Error::new(ErrorKind::NotFound, "metadata: entry not found"))?;
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/usr/bin
+            env JAVA_HOME=/opt/jdk/bin
+            executable /usr/bin/java
+            executable /opt/jdk/bin/java
+            executable /opt/jdk/bin/javac
+            executable /opt/tools:2/tool
+            ",
+        )
+        .unwrap();
+        let search = |query: &str| {
+            which::WhichConfig::new_with_sys(&sys)
+                .query(query.parse().unwrap())
+                .all_results()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search("java"), vec![PathBuf::from("/usr/bin/java")]);
+        assert_eq!(
+            search("$JAVA_HOME:java*"),
+            vec![
+                PathBuf::from("/opt/jdk/bin/java"),
+                PathBuf::from("/opt/jdk/bin/javac")
+            ]
+        );
+        assert_eq!(
+            search("/opt/jdk/bin:java"),
+            vec![PathBuf::from("/opt/jdk/bin/java")]
+        );
+        // The scope may contain colons.
+        assert_eq!(
+            search("/opt/tools:2:tool"),
+            vec![PathBuf::from("/opt/tools:2/tool")]
+        );
 
-            Ok(entry.as_metadata())
-        }
+        // The example from the `Query` docs.
+        let tools = PathBuf::from(r"C:\tools");
+        let sys = InMemorySys::windows()
+            .env_var("PATH", "/usr/bin")
+            .executable(tools.join("cargo.exe"))
+            .executable(tools.join("notes.txt"));
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .query(r"C:\tools:*.exe".parse().unwrap())
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![tools.join("cargo.exe")]);
+    }
+
+    #[test]
+    fn resolve_many() {
+        let sys = InMemorySys::from_fixture(
+            r"
+            cwd /src
+            env PATH=/a:/b:/a
+            executable /a/cc
+            file /a/make
+            executable /b/cc
+            executable /b/make
+            executable /src/local
+            ",
+        )
+        .unwrap();
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .resolve_many(["cc", "make", "missing", "./local", "cc"])
+            .unwrap();
+        let expected = [
+            ("./local", vec!["/src/local"]),
+            ("cc", vec!["/a/cc", "/b/cc"]),
+            ("make", vec!["/b/make"]),
+            ("missing", vec![]),
+        ]
+        .into_iter()
+        .map(|(name, paths)| {
+            (
+                OsString::from(name),
+                paths.into_iter().map(PathBuf::from).collect::<Vec<_>>(),
+            )
+        })
+        .collect::<std::collections::BTreeMap<_, _>>();
+        assert_eq!(result, expected);
+
+        let sys = InMemorySys::windows()
+            .env_var("PATH", "/tools")
+            .executable("/tools/python.cmd")
+            .executable("/tools/python.exe");
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .resolve_many(["python", "PYTHON", "python.cmd"])
+            .unwrap();
+        let both = vec![
+            PathBuf::from("/tools/python.exe"),
+            PathBuf::from("/tools/python.cmd"),
+        ];
+        assert_eq!(result[&OsString::from("python")], both);
+        assert_eq!(result[&OsString::from("PYTHON")], both);
+        assert_eq!(
+            result[&OsString::from("python.cmd")],
+            vec![PathBuf::from("/tools/python.cmd")]
+        );
+
+        // Names with an extension that isn't in `PATHEXT` are found as is, then with each
+        // extension appended, like a single lookup.
+        let sys = InMemorySys::windows()
+            .env_var("PATH", "/tools")
+            .executable("/tools/foo.py.exe")
+            .executable("/tools/foo.py");
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .resolve_many(["foo.py", "foo"])
+            .unwrap();
+        let single = which::WhichConfig::new_with_sys(&sys)
+            .binary_name("foo.py".into())
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            single,
+            vec![
+                PathBuf::from("/tools/foo.py"),
+                PathBuf::from("/tools/foo.py.exe")
+            ]
+        );
+        assert_eq!(result[&OsString::from("foo.py")], single);
+        assert!(result[&OsString::from("foo")].is_empty());
+    }
+
+    #[test]
+    fn resolve_many_refuse_relative_path_entries() {
+        let sys = InMemorySys::from_fixture(
+            r"
+            cwd /project
+            env PATH=bin::/usr/bin
+            executable /project/bin/tool
+            executable /project/tool
+            executable /usr/bin/tool
+            ",
+        )
+        .unwrap();
 
-        fn symlink_metadata(&self, path: &Path) -> io::Result<Self::Metadata> {
-            let entry = self
-                .get_entry(path)
-                .ok_or_else(|| Error::new(ErrorKind::NotFound, "metadata: entry not found"))?;
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .resolve_many(["tool"])
+            .unwrap();
+        assert_eq!(
+            result[&OsString::from("tool")],
+            vec![
+                PathBuf::from("bin/tool"),
+                PathBuf::from("tool"),
+                PathBuf::from("/usr/bin/tool")
+            ]
+        );
 
-            Ok(entry.as_metadata())
+        let mut refused = Vec::new();
+        let result = which::WhichConfig::new_with_sys(&sys)
+            .refuse_relative_path_entries(true)
+            .nonfatal_error_handler(|e| {
+                if let which::NonFatalError::FoundRelativeToCwd(path) = e {
+                    refused.push(path);
+                }
+            })
+            .resolve_many(["tool", "./tool"])
+            .unwrap();
+        assert_eq!(
+            result[&OsString::from("tool")],
+            vec![PathBuf::from("/usr/bin/tool")]
+        );
+        assert_eq!(
+            refused,
+            vec![PathBuf::from("bin/tool"), PathBuf::from("tool")]
+        );
+        // Explicitly relative names aren't affected.
+        assert_eq!(
+            result[&OsString::from("./tool")],
+            vec![PathBuf::from("/project/tool")]
+        );
+    }
+
+    #[test]
+    fn resolver() {
+        let sys = InMemorySys::from_fixture(
+            r"
+            home /home/user
+            env PATH=~/bin:/usr/bin
+            executable /home/user/bin/tool
+            executable /usr/bin/tool
+            executable /usr/bin/cc
+            executable /usr/bin/make
+            ",
+        )
+        .unwrap();
+        let mut rejected = Vec::new();
+        let resolver = which::WhichConfig::new_with_sys(&sys)
+            .filter(|path: &Path| path != Path::new("/usr/bin/make"))
+            .nonfatal_error_handler(|e| {
+                if let which::NonFatalError::RejectedByFilter(path) = e {
+                    rejected.push(path);
+                }
+            })
+            .resolver();
+
+        let found = std::thread::scope(|s| {
+            let threads = ["tool", "cc", "make"].map(|name| {
+                let resolver = &resolver;
+                s.spawn(move || resolver.resolve(name).ok())
+            });
+            threads.map(|t| t.join().unwrap())
+        });
+        assert_eq!(
+            found,
+            [
+                Some(PathBuf::from("/home/user/bin/tool")),
+                Some(PathBuf::from("/usr/bin/cc")),
+                None
+            ]
+        );
+        assert_eq!(
+            resolver.resolve_all("tool").unwrap().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/home/user/bin/tool"),
+                PathBuf::from("/usr/bin/tool")
+            ]
+        );
+        match resolver.resolve("missing").unwrap_err() {
+            which::Error::CannotFindBinaryPath(context) => assert_eq!(
+                context.paths,
+                vec![PathBuf::from("/home/user/bin"), PathBuf::from("/usr/bin")]
+            ),
+            e => panic!("unexpected error {e:?}"),
         }
+        drop(resolver);
+        assert_eq!(rejected, vec![PathBuf::from("/usr/bin/make")]);
+    }
 
-        fn read_dir(
-            &self,
-            path: &Path,
-        ) -> io::Result<Box<dyn Iterator<Item = io::Result<Self::ReadDirEntry>>>> {
-            let entry = self
-                .get_entry_follow_symlink(path)
-                .ok_or_else(|| Error::new(ErrorKind::NotFound, "metadata: entry not found"))?;
-
-            match &entry {
-                DirectoryEntry::Directory(dir) => {
-                    let entries = dir
-                        .entries
-                        .keys()
-                        .map(|name| {
-                            Ok(ReadDirEntry {
-                                path: path.join(name),
-                            })
-                        })
-                        .collect::<Vec<_>>();
-                    Ok(Box::new(entries.into_iter()))
+    #[test]
+    fn caching_resolver() {
+        use std::cell::Cell;
+        use std::time::Duration;
+
+        let sys = InMemorySys::new()
+            .env_var("PATH", "/usr/bin")
+            .cwd("/work")
+            .executable("/usr/bin/cc")
+            .executable("/work/run");
+        let probes = Cell::new(0);
+        let filter = |_: &Path| {
+            probes.set(probes.get() + 1);
+            true
+        };
+        // The in-memory file system has no modification times, so entries are only kept with a
+        // ttl, until they're that old.
+        let resolver = which::WhichConfig::new_with_sys(&sys)
+            .filter(filter)
+            .caching_resolver()
+            .ttl(Duration::from_secs(3600));
+
+        for _ in 0..3 {
+            assert_eq!(
+                resolver.resolve("cc").unwrap(),
+                PathBuf::from("/usr/bin/cc")
+            );
+        }
+        assert_eq!(probes.get(), 1);
+        assert_eq!(
+            resolver.resolve_all("cc").unwrap().collect::<Vec<_>>(),
+            vec![PathBuf::from("/usr/bin/cc")]
+        );
+        assert_eq!(probes.get(), 2);
+        for _ in 0..2 {
+            assert_eq!(
+                resolver.resolve("./run").unwrap(),
+                PathBuf::from("/work/run")
+            );
+            assert!(matches!(
+                resolver.resolve("missing"),
+                Err(which::Error::CannotFindBinaryPath(_))
+            ));
+        }
+        assert_eq!(probes.get(), 3);
+
+        resolver.rehash();
+        resolver.resolve("cc").unwrap();
+        assert_eq!(probes.get(), 4);
+
+        let resolver = which::WhichConfig::new_with_sys(&sys)
+            .filter(filter)
+            .caching_resolver()
+            .ttl(Duration::ZERO);
+        resolver.resolve("cc").unwrap();
+        resolver.resolve("cc").unwrap();
+        assert_eq!(probes.get(), 6);
+
+        let resolver = which::WhichConfig::new_with_sys(&sys)
+            .filter(filter)
+            .caching_resolver();
+        resolver.resolve("cc").unwrap();
+        resolver.resolve("cc").unwrap();
+        assert_eq!(probes.get(), 8);
+    }
+
+    #[test]
+    fn parallel() {
+        use std::num::NonZeroUsize;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Condvar, Mutex};
+        use std::time::Duration;
+        use which::sys::Sys;
+        use which::{CandidateOutcome, Checker, DefaultChecker};
+
+        /// Takes a while to check anything below `/slow`, and counts the checks.
+        #[derive(Clone, Default)]
+        struct Slow(Arc<AtomicUsize>);
+
+        impl Checker for Slow {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                if path.starts_with("/slow") {
+                    std::thread::sleep(Duration::from_millis(20));
                 }
-                // should use ErrorKind::NotADirectory once upgrading rust version
-                _ => Err(Error::new(ErrorKind::Other, "Not a directory")),
+                DefaultChecker.check(sys, path)
             }
         }
 
-        fn is_valid_executable(&self, path: &Path) -> io::Result<bool> {
-            let entry = self.get_entry_follow_symlink(path).ok_or_else(|| {
-                Error::new(ErrorKind::NotFound, "is_valid_executable: entry not found")
-            })?;
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/slow/0:/fast/1:/slow/2:/fast/3:/slow/4:/slow/5:/slow/6:/slow/7
+            executable /fast/1/tool
+            executable /fast/3/tool
+            executable /slow/4/tool
+            executable /slow/7/tool
+            ",
+        )
+        .unwrap();
+        let threads = NonZeroUsize::new(4).unwrap();
+        let config = || {
+            which::WhichConfig::new_with_sys(sys.clone())
+                .binary_name("tool".into())
+                .checker(Slow::default())
+                .parallel(threads)
+        };
 
-            match &entry {
-                DirectoryEntry::File(file) => Ok(file.is_valid_executable),
-                _ => Ok(false),
+        assert_eq!(
+            config().first_result().unwrap(),
+            PathBuf::from("/fast/1/tool")
+        );
+        assert_eq!(
+            config().all_results().unwrap().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/fast/1/tool"),
+                PathBuf::from("/fast/3/tool"),
+                PathBuf::from("/slow/4/tool"),
+                PathBuf::from("/slow/7/tool"),
+            ]
+        );
+        let rejected = std::cell::RefCell::new(Vec::new());
+        let found = config()
+            .filter(|path: &Path| !path.starts_with("/fast"))
+            .nonfatal_error_handler(|e| {
+                if let which::NonFatalError::RejectedByFilter(path) = e {
+                    rejected.borrow_mut().push(path);
+                }
+            })
+            .first_result();
+        assert_eq!(found.unwrap(), PathBuf::from("/slow/4/tool"));
+        assert_eq!(
+            rejected.into_inner(),
+            vec![PathBuf::from("/fast/1/tool"), PathBuf::from("/fast/3/tool")]
+        );
+        // The checker can be set after the parallel mode too.
+        let found = which::WhichConfig::new_with_sys(sys.clone())
+            .binary_name("tool".into())
+            .parallel(threads)
+            .checker(Slow::default())
+            .filter(|path: &Path| path.starts_with("/slow"))
+            .first_result();
+        assert_eq!(found.unwrap(), PathBuf::from("/slow/4/tool"));
+
+        // Nothing after the earliest match is handed out once it's confirmed.
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/fast/0:/slow/1:/slow/2:/slow/3:/slow/4:/slow/5:/slow/6:/slow/7
+            executable /fast/0/tool
+            ",
+        )
+        .unwrap();
+        let checker = Slow::default();
+        let found = which::WhichConfig::new_with_sys(sys)
+            .binary_name("tool".into())
+            .checker(checker.clone())
+            .parallel(NonZeroUsize::new(2).unwrap())
+            .first_result();
+        assert_eq!(found.unwrap(), PathBuf::from("/fast/0/tool"));
+        assert!(checker.0.load(Ordering::Relaxed) <= 2);
+
+        /// Doesn't finish checking anything below `/hang` until it's opened.
+        #[derive(Clone, Default)]
+        struct Hang(Arc<(Mutex<bool>, Condvar)>);
+
+        impl Checker for Hang {
+            fn check<TSys: Sys>(&self, sys: TSys, path: &Path) -> CandidateOutcome {
+                if path.starts_with("/hang") {
+                    let (open, wake) = &*self.0;
+                    let mut open = open.lock().unwrap();
+                    while !*open {
+                        open = wake.wait(open).unwrap();
+                    }
+                }
+                DefaultChecker.check(sys, path)
             }
         }
+
+        // A check still running after the earliest match doesn't hold up the result.
+        let sys = InMemorySys::from_fixture(
+            r"
+            env PATH=/fast/0:/hang/1
+            executable /fast/0/tool
+            executable /hang/1/tool
+            ",
+        )
+        .unwrap();
+        let checker = Hang::default();
+        let found = which::WhichConfig::new_with_sys(sys)
+            .binary_name("tool".into())
+            .checker(checker.clone())
+            .parallel(NonZeroUsize::new(2).unwrap())
+            .first_result();
+        assert_eq!(found.unwrap(), PathBuf::from("/fast/0/tool"));
+        *checker.0 .0.lock().unwrap() = true;
+        checker.0 .1.notify_all();
     }
 
     #[test]
-    fn basic() {
-        let mut sys = InMemorySys::new();
-        sys.set_env_var("PATH", "/sub/dir1/:/sub/dir2/");
-        sys.write_non_executable("/sub/dir1/exec1");
-        sys.write_executable("/sub/dir2/exec1"); // will get this one
-        sys.write_executable("/sub/dir2/exec2");
-        let config = which::WhichConfig::new_with_sys(sys).binary_name(OsString::from("exec1"));
-        let result = config.first_result().unwrap();
-        assert_eq!(result, PathBuf::from("/sub/dir2/exec1"));
+    fn library_runpath_and_cache() {
+        let lib = elf(EM_X86_64, None, None);
+        let sys = InMemorySys::new()
+            .env_var("LD_LIBRARY_PATH", "/custom")
+            .file_with_contents(
+                "/opt/app/bin/app",
+                elf(EM_X86_64, Some("/ignored"), Some("$ORIGIN/../lib")),
+            )
+            .file_with_contents("/ignored/libfoo.so.1", lib.clone())
+            .file_with_contents("/opt/app/lib/libfoo.so.1", lib.clone())
+            .file_with_contents("/custom/libfoo.so.1", elf(EM_AARCH64, None, None))
+            .file_with_contents("/usr/lib/x86_64-linux-gnu/libfoo.so.1", lib.clone())
+            .file_with_contents("/usr/lib/libfoo.so.1", lib)
+            .file_with_contents(
+                "/etc/ld.so.cache",
+                ld_so_cache(&[
+                    (FLAGS_X86_64, "libbar.so", "/usr/lib/libbar.so"),
+                    (
+                        FLAGS_X86_64,
+                        "libfoo.so.1",
+                        "/usr/lib/x86_64-linux-gnu/libfoo.so.1",
+                    ),
+                ]),
+            );
+        let config = || {
+            which::LibraryConfig::new_with_sys(&sys)
+                .name("libfoo.so.1".into())
+                .object("/opt/app/bin/app".into())
+        };
+
+        assert_eq!(
+            config().all_results().unwrap().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/opt/app/bin/../lib/libfoo.so.1"),
+                PathBuf::from("/usr/lib/x86_64-linux-gnu/libfoo.so.1"),
+            ]
+        );
+
+        // Without the object or a running program to read, nothing is known about the libraries
+        // that can be loaded.
+        let result = which::LibraryConfig::new_with_sys(&sys)
+            .name("libfoo.so.1".into())
+            .first_result()
+            .unwrap();
+        assert_eq!(result, PathBuf::from("/custom/libfoo.so.1"));
+
+        let result = which::LibraryConfig::new_with_sys(&sys)
+            .name("libmissing.so".into())
+            .object("/opt/app/bin/app".into())
+            .first_result();
+        match result {
+            Err(which::Error::CannotFindBinaryPath(context)) => {
+                assert_eq!(context.query, Some(OsString::from("libmissing.so")));
+                assert_eq!(
+                    context.paths[..2],
+                    [
+                        PathBuf::from("/custom"),
+                        PathBuf::from("/opt/app/bin/../lib")
+                    ]
+                );
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        let result = which::LibraryConfig::new_with_sys(&sys)
+            .name("libfoo.so.1".into())
+            .object("/usr/lib/missing".into())
+            .first_result();
+        assert!(matches!(result, Err(which::Error::CannotReadObject { .. })));
     }
 
     #[test]
-    fn symlink() {
-        let mut sys = InMemorySys::new();
-        sys.set_env_var("PATH", "/sub/dir1/");
-        sys.create_symlink("/sub/dir1/exec", "/sub/dir2/exec");
-        sys.write_executable("/sub/dir2/exec");
-        let config = which::WhichConfig::new_with_sys(sys).binary_name(OsString::from("exec"));
-        let result = config.first_result().unwrap();
-        assert_eq!(result, PathBuf::from("/sub/dir1/exec"));
+    fn library_multiarch() {
+        let lib = elf(EM_X86_64, None, None);
+        let sys = InMemorySys::new()
+            .env_var("LD_LIBRARY_PATH", "/custom")
+            .file_with_contents("/proc/self/exe", elf(EM_X86_64, None, None))
+            .file_with_contents("/custom/libc.so.6", elf(EM_AARCH64, None, None))
+            .file_with_contents("/usr/lib32/libc.so.6", lib.clone())
+            .file_with_contents("/usr/libx32/libc.so.6", lib.clone())
+            .file_with_contents("/usr/lib/x86_64-linux-gnu/libc.so.6", lib.clone())
+            .file_with_contents("/lib64/libc.so.6", lib)
+            .file_with_contents(
+                "/etc/ld.so.cache",
+                ld_so_cache(&[
+                    (FLAGS_I386, "libc.so.6", "/usr/lib32/libc.so.6"),
+                    (FLAGS_X32, "libc.so.6", "/usr/libx32/libc.so.6"),
+                    (
+                        FLAGS_X86_64,
+                        "libc.so.6",
+                        "/usr/lib/x86_64-linux-gnu/libc.so.6",
+                    ),
+                ]),
+            );
+
+        // Without an object, only libraries the running program could load are found.
+        let result = which::LibraryConfig::new_with_sys(&sys)
+            .name("libc.so.6".into())
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                PathBuf::from("/usr/lib/x86_64-linux-gnu/libc.so.6"),
+                PathBuf::from("/lib64/libc.so.6"),
+            ]
+        );
     }
 
     #[test]
-    fn tilde_path() {
-        let mut sys = InMemorySys::new();
-        sys.set_home_dir("/home/user/");
-        sys.set_env_var("PATH", "/dir/:~/sub/");
-        sys.write_executable("/home/user/sub/exec");
-        let config = which::WhichConfig::new_with_sys(sys).binary_name(OsString::from("exec"));
-        let result = config.first_result().unwrap();
-        assert_eq!(result, PathBuf::from("/home/user/sub/exec"));
+    fn library_rpath_and_conf() {
+        let lib = elf(EM_X86_64, None, None);
+        let sys = InMemorySys::new()
+            .env_var("LD_LIBRARY_PATH", "/llp")
+            .file_with_contents("/app", elf(EM_X86_64, Some("/rpath:$ORIGIN/lib"), None))
+            .file_with_contents("/rpath/libz.so", lib.clone())
+            .file_with_contents("/lib/libz.so", lib.clone())
+            .file_with_contents("/llp/libz.so", lib.clone())
+            .file_with_contents("/opt/z/libz.so", lib.clone())
+            .file_with_contents("/usr/lib64/libz.so", lib)
+            .file_with_contents(
+                "/etc/ld.so.conf",
+                "# comment\ninclude ld.so.conf.d/*.conf\n/opt/missing\n",
+            )
+            .file_with_contents("/etc/ld.so.conf.d/z.conf", "/opt/z\n")
+            .file_with_contents("/etc/ld.so.conf.d/z.conf.bak", "/opt/ignored\n");
+
+        let config = || {
+            which::LibraryConfig::new_with_sys(&sys)
+                .name("libz.so".into())
+                .object("/app".into())
+        };
+        let result = config().all_results().unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                PathBuf::from("/rpath/libz.so"),
+                PathBuf::from("/lib/libz.so"),
+                PathBuf::from("/llp/libz.so"),
+                PathBuf::from("/usr/lib64/libz.so"),
+            ]
+        );
+
+        let result = config()
+            .ld_so_conf_fallback(true)
+            .all_results()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                PathBuf::from("/rpath/libz.so"),
+                PathBuf::from("/lib/libz.so"),
+                PathBuf::from("/llp/libz.so"),
+                PathBuf::from("/opt/z/libz.so"),
+                PathBuf::from("/usr/lib64/libz.so"),
+            ]
+        );
     }
 }
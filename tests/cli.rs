@@ -0,0 +1,165 @@
+//! Tests for the GNU `which` compatible binary.
+#![cfg(all(unix, feature = "cli"))]
+#![allow(clippy::disallowed_methods)]
+
+use std::fs;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+fn mk_bin(dir: &Path, name: &str) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    let bin = dir.join(name);
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o755)
+        .open(&bin)
+        .unwrap();
+    bin
+}
+
+fn which(path: &str, cwd: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_which"))
+        .args(args)
+        .env("PATH", path)
+        .current_dir(cwd)
+        .output()
+        .unwrap()
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+    String::from_utf8(output.stdout.clone())
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn first_and_all() {
+    let tmp = TempDir::new().unwrap();
+    let a = mk_bin(&tmp.path().join("a"), "tool");
+    let b = mk_bin(&tmp.path().join("b"), "tool");
+    let path = format!(
+        "{}:{}",
+        tmp.path().join("a").display(),
+        tmp.path().join("b").display()
+    );
+
+    let output = which(&path, tmp.path(), &["tool"]);
+    assert!(output.status.success());
+    assert_eq!(stdout_lines(&output), vec![a.display().to_string()]);
+
+    let output = which(&path, tmp.path(), &["-a", "tool"]);
+    assert_eq!(
+        stdout_lines(&output),
+        vec![a.display().to_string(), b.display().to_string()]
+    );
+}
+
+#[test]
+fn exit_code_counts_missing_names() {
+    let tmp = TempDir::new().unwrap();
+    mk_bin(&tmp.path().join("a"), "tool");
+    let path = tmp.path().join("a").display().to_string();
+
+    let output = which(&path, tmp.path(), &["tool", "missing1", "missing2"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("which: no missing1 in ({path})")));
+
+    let output = which(&path, tmp.path(), &[]);
+    assert_eq!(output.status.code(), Some(255));
+}
+
+#[test]
+fn dot_entries() {
+    let tmp = TempDir::new().unwrap();
+    let bin = mk_bin(&tmp.path().join("sub"), "tool");
+    let cwd = tmp.path().join("sub");
+
+    let output = which(".", &cwd, &["tool"]);
+    assert_eq!(stdout_lines(&output), vec![bin.display().to_string()]);
+
+    let output = which(".", &cwd, &["--show-dot", "tool"]);
+    assert_eq!(stdout_lines(&output), vec!["./tool".to_owned()]);
+
+    let output = which(".", &cwd, &["--skip-dot", "tool"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn tty_only_ignores_following_options() {
+    let tmp = TempDir::new().unwrap();
+    mk_bin(&tmp.path().join("a"), "tool");
+    mk_bin(&tmp.path().join("b"), "tool");
+    let path = format!(
+        "{}:{}",
+        tmp.path().join("a").display(),
+        tmp.path().join("b").display()
+    );
+
+    // The test harness captures stdout, so it is never a tty.
+    let output = which(&path, tmp.path(), &["--tty-only", "-a", "tool"]);
+    assert_eq!(stdout_lines(&output).len(), 1);
+}
+
+#[test]
+fn json_output() {
+    let tmp = TempDir::new().unwrap();
+    let bin = mk_bin(&tmp.path().join("a"), "tool");
+    let path = tmp.path().join("a").display().to_string();
+
+    let output = which(&path, tmp.path(), &["--json", "tool", "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        format!(
+            "[{{\"name\":\"tool\",\"found\":true,\"paths\":[\"{}\"]}},{{\"name\":\"missing\",\"found\":false,\"paths\":[]}}]",
+            bin.display()
+        )
+    );
+}
+
+#[test]
+fn scoped_queries() {
+    let tmp = TempDir::new().unwrap();
+    mk_bin(&tmp.path().join("a"), "gradle");
+    let b = mk_bin(&tmp.path().join("b"), "gradle");
+    let gradlew = mk_bin(&tmp.path().join("b"), "gradlew");
+    let path = tmp.path().join("a").display().to_string();
+    let dir = tmp.path().join("b").display().to_string();
+
+    let output = which(&path, tmp.path(), &["--scoped", &format!("{dir}:gradle")]);
+    assert_eq!(stdout_lines(&output), vec![b.display().to_string()]);
+
+    // Directory entries come in no particular order.
+    let output = which(
+        &path,
+        tmp.path(),
+        &["-a", "--scoped", &format!("{dir}:gradle*")],
+    );
+    let mut lines = stdout_lines(&output);
+    lines.sort();
+    assert_eq!(
+        lines,
+        vec![b.display().to_string(), gradlew.display().to_string()]
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_which"))
+        .args(["--scoped", "$TOOLS:gradlew"])
+        .env("PATH", &path)
+        .env("TOOLS", &dir)
+        .output()
+        .unwrap();
+    assert_eq!(stdout_lines(&output), vec![gradlew.display().to_string()]);
+
+    // Without `--scoped`, a `:` is just part of the command name.
+    let tool = mk_bin(&tmp.path().join("a"), "b:gradle");
+    let output = which(&path, tmp.path(), &["b:gradle"]);
+    assert_eq!(stdout_lines(&output), vec![tool.display().to_string()]);
+}